@@ -0,0 +1,124 @@
+//! The announcer, who calls out the best bits of a match. It listens to the same events as the
+//! sound effects, and after each line it keeps quiet for a while so it doesn't talk over itself.
+
+use agb::fixnum::FixedNum;
+use agb::include_wav;
+use agb::sound::mixer::SoundData;
+
+use crate::event::GameEvent;
+use crate::fixed;
+
+static NICE: SoundData = include_wav!("sfx/voice-nice.wav");
+static SMASH: SoundData = include_wav!("sfx/voice-smash.wav");
+static POINT: SoundData = include_wav!("sfx/voice-point.wav");
+
+/// How many steps the announcer waits after saying something before it says anything else
+const COOLDOWN_STEPS: u16 = 90;
+/// A return this close to the ball's top speed, from 0 to 1, counts as a smash
+const SMASH_SPEED: FixedNum<8> = fixed(0.9);
+/// The rally lengths that get called out, and what's said when the rally gets there
+const RALLY_LINES: [(u32, VoiceLine); 3] = [
+    (8, VoiceLine::Nice),
+    (16, VoiceLine::Nice),
+    (24, VoiceLine::Smash),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum VoiceLine {
+    Nice,
+    Smash,
+    Point,
+}
+
+impl VoiceLine {
+    pub fn sound(self) -> SoundData {
+        match self {
+            VoiceLine::Nice => NICE,
+            VoiceLine::Smash => SMASH,
+            VoiceLine::Point => POINT,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Announcer {
+    /// Steps left before the announcer can speak again
+    cooldown: u16,
+}
+
+impl Announcer {
+    /// Counts down the cooldown by a step
+    pub fn update(&mut self) {
+        self.cooldown = self.cooldown.saturating_sub(1);
+    }
+
+    /// What to say about `event`, if anything, now that the rally's got to `rally` hits
+    pub fn react(&mut self, event: &GameEvent, rally: u32) -> Option<VoiceLine> {
+        if self.cooldown > 0 {
+            return None;
+        }
+
+        let line = match *event {
+            GameEvent::PaddleHit { speed, .. } => RALLY_LINES
+                .iter()
+                .find(|&&(length, _)| length == rally)
+                .map(|&(_, line)| line)
+                .or((speed >= SMASH_SPEED).then_some(VoiceLine::Smash)),
+            GameEvent::Score { .. } => Some(VoiceLine::Point),
+            GameEvent::WallHit { .. } | GameEvent::Bounce { .. } | GameEvent::BrickHit { .. } => {
+                None
+            }
+        }?;
+
+        self.cooldown = COOLDOWN_STEPS;
+        Some(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Side;
+    use agb::fixnum::{num, vec2};
+
+    fn hit(speed: FixedNum<8>) -> GameEvent {
+        GameEvent::PaddleHit {
+            side: Side::Left,
+            pos: vec2(num!(16), num!(80)),
+            speed,
+        }
+    }
+
+    #[test_case]
+    fn long_rallies_get_called_out_once_each(_gba: &mut agb::Gba) {
+        let mut announcer = Announcer::default();
+        let said: alloc::vec::Vec<_> = (1..=30)
+            .filter_map(|rally| {
+                // Far enough apart for the cooldown to run out between the hits
+                for _ in 0..COOLDOWN_STEPS {
+                    announcer.update();
+                }
+                announcer.react(&hit(num!(0.5)), rally)
+            })
+            .collect();
+
+        assert!(said == [VoiceLine::Nice, VoiceLine::Nice, VoiceLine::Smash]);
+    }
+
+    #[test_case]
+    fn announcer_waits_before_speaking_again(_gba: &mut agb::Gba) {
+        let mut announcer = Announcer::default();
+        let score = GameEvent::Score { side: Side::Right };
+
+        assert!(announcer.react(&hit(num!(1)), 3) == Some(VoiceLine::Smash));
+        assert!(
+            announcer.react(&score, 0).is_none(),
+            "too soon after the smash"
+        );
+
+        for _ in 0..COOLDOWN_STEPS {
+            announcer.update();
+        }
+        assert!(announcer.react(&score, 0) == Some(VoiceLine::Point));
+    }
+}
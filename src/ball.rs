@@ -0,0 +1,1837 @@
+//! The ball, and how it moves and bounces off the walls and paddles.
+
+use agb::display::Priority;
+use agb::display::object::Object;
+use agb::fixnum::{FixedNum, Rect, Vector2D, num, vec2};
+use agb::input::Button;
+use alloc::vec::Vec;
+
+use crate::brick::Brick;
+use crate::collision::{Circle, Touches};
+use crate::event::{EventSink, GameEvent};
+use crate::input::Input;
+use crate::obstacle::Obstacle;
+use crate::paddle::Paddle;
+use crate::rng::Rng;
+use crate::sprite_budget::{Decoration, ObjectSink, SpriteBudget};
+use crate::{Side, fixed, sprites};
+
+/// The fastest the ball can travel, as a multiple of the speed it starts each point at. Any faster
+/// and it's too quick to react to. Going by the starting speed means a faster ball on a harder
+/// difficulty has a higher top speed to match.
+const MAX_SPEEDUP: FixedNum<8> = fixed(2.5);
+/// The slowest the ball is allowed to move horizontally after its speed has been capped, also as
+/// a multiple of its starting speed
+const MIN_X_SPEED: FixedNum<8> = fixed(0.25);
+/// How much faster the ball gets with every paddle hit, until it reaches its top speed
+const BALL_SPEEDUP: FixedNum<8> = fixed(1.05);
+/// The ball's vertical speed is kept between these multiples of its horizontal speed, so it can't
+/// end up travelling almost flat or almost straight up and down
+const MIN_BALL_ANGLE: FixedNum<8> = fixed(0.125);
+const MAX_BALL_ANGLE: FixedNum<8> = fixed(1.5);
+/// How steeply a hit right on the end of a paddle sends the ball away, as a multiple of its
+/// horizontal speed. Hits further in come off flatter, down to straight across in the middle.
+const MAX_DEFLECTION: FixedNum<8> = fixed(1.25);
+/// How much faster a fully charged power shot sends the ball back, on top of the usual speed up
+const MAX_CHARGE_BOOST: FixedNum<8> = fixed(0.5);
+/// How much of the paddle's movement gets passed on to the ball when it's hit
+const PADDLE_NUDGE: FixedNum<8> = fixed(0.25);
+/// How much spin a paddle hit puts on the ball for each pixel the paddle was moving, and for
+/// landing right on the end of it. Spin turns the ball by this many radians every frame. They're
+/// only a few 256ths of a radian, so they're written out raw rather than rounded off by `fixed`.
+const PADDLE_SPIN: FixedNum<8> = FixedNum::from_raw(4);
+const OFFSET_SPIN: FixedNum<8> = FixedNum::from_raw(6);
+/// How much spin wears off every frame, so curving shots straighten out. A hit with the most spin
+/// keeps curving for about a quarter of a second and turns the ball by over 20 degrees.
+const SPIN_DECAY: FixedNum<8> = FixedNum::from_raw(1);
+/// How much of a moving obstacle's movement gets passed on to the ball when it bounces off
+const OBSTACLE_SPIN: FixedNum<8> = fixed(0.5);
+/// Any slower than this and the ball counts as stopped. Nothing should ever stop it, but if the
+/// spin, wind and speed limits ever manage it between them the match would never end.
+const STALL_SPEED: FixedNum<8> = fixed(0.0625);
+/// A rally this many hits longer than when the watchdog last stepped in, or this many steps of
+/// play without a point, counts as stuck. Both take minutes of play, far more than any real
+/// rally, but the CPU playing itself could in theory settle into a bounce that repeats forever.
+const WATCHDOG_RALLY: u32 = 500;
+const WATCHDOG_STEPS: u32 = 60 * 60 * 5;
+/// How far the watchdog turns a stuck ball, in radians either way
+const WATCHDOG_NUDGE: (FixedNum<8>, FixedNum<8>) = (fixed(0.1), fixed(0.2));
+/// The furthest the ball moves between collision checks. A ball going faster than this in a frame
+/// gets checked several times along the way, so it can't skip straight past a paddle.
+const SWEEP_STEP: FixedNum<8> = fixed(4.);
+
+/// The size of the normal ball, and the larger one that's easier to see
+pub const BALL_RADIUS: FixedNum<8> = fixed(8.);
+pub const LARGE_BALL_RADIUS: FixedNum<8> = fixed(12.);
+
+/// The whole screen, which is where the game's played unless the config frames it with margins
+pub fn full_court() -> Rect<FixedNum<8>> {
+    Rect::new(
+        vec2(num!(0), num!(0)),
+        vec2(num!(agb::display::WIDTH), num!(agb::display::HEIGHT)),
+    )
+}
+
+/// How long the ball and paddle stay lit up after hitting each other
+pub const HIT_FLASH_FRAMES: u8 = 4;
+
+/// How many ghost images follow the ball around, kept small so there are enough objects to go
+/// around with several balls in play
+const TRAIL_LENGTH: usize = 4;
+
+/// How long the CPU holds on to the ball before serving it
+const CPU_SERVE_DELAY: u16 = 60;
+/// The steepest a serve can go, as the vertical speed over the horizontal. Serves go out at a
+/// random angle up to this far up or down, but never flatter than `MIN_BALL_ANGLE`.
+const MAX_SERVE_ANGLE: FixedNum<8> = fixed(0.5);
+/// How much holding up or down tips the player's serve each frame while they aim it. Aiming goes
+/// up to `MAX_SERVE_ANGLE` either way, so the serve always heads out across the court.
+const AIM_SPEED: FixedNum<8> = fixed(0.03125);
+
+/// What the ball is up against on the right hand side of the field
+pub enum Opponent<'a> {
+    Paddle(&'a mut Paddle),
+    /// A wall of bricks to knock down, with a solid wall behind them instead of a goal
+    Bricks(&'a mut Vec<Brick>),
+}
+
+impl Opponent<'_> {
+    fn touches(&self, mask: &Circle<FixedNum<8>>) -> bool {
+        match self {
+            Opponent::Paddle(paddle) => mask.touches(paddle.collision_rect()),
+            Opponent::Bricks(bricks) => bricks
+                .iter()
+                .any(|brick| mask.touches(brick.collision_rect())),
+        }
+    }
+}
+
+/// What happens when the ball reaches the top or bottom of the screen
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// It bounces back like it's hit a wall
+    Bounce,
+    /// It carries on through and comes back in from the opposite edge
+    Wrap,
+}
+
+impl EdgeMode {
+    /// The other mode
+    pub fn toggled(self) -> Self {
+        match self {
+            EdgeMode::Bounce => EdgeMode::Wrap,
+            EdgeMode::Wrap => EdgeMode::Bounce,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BallState {
+    /// Stuck to the front of the paddle on `side`, waiting to be served. `held_for` counts the
+    /// frames spent waiting, and `aim_angle` is how steeply the serve will go, as its speed up or
+    /// down over its speed out.
+    Held {
+        side: Side,
+        held_for: u16,
+        aim_angle: FixedNum<8>,
+    },
+    InPlay,
+}
+
+pub struct Ball {
+    state: BallState,
+    pos: Vector2D<FixedNum<8>>,
+    velocity: Vector2D<FixedNum<8>>,
+    /// The velocity the ball starts each point with, before any speedup
+    base_velocity: Vector2D<FixedNum<8>>,
+    /// The number of paddle hits since the last point was scored
+    rally: u32,
+    /// Which paddle hit the ball most recently this point, and so gets the credit for any power
+    /// ups it collects
+    last_hit: Option<Side>,
+    /// Where the ball has been over the last few frames, as a ring buffer with the oldest position
+    /// at `trail_next`
+    trail: [Vector2D<FixedNum<8>>; TRAIL_LENGTH],
+    trail_next: usize,
+    /// Frames left showing the ball lit up after hitting a paddle
+    hit_flash: u8,
+    /// Added to the velocity every frame, so the ball curves as it goes
+    gravity: Vector2D<FixedNum<8>>,
+    /// Added on top of the gravity while the wind is blowing
+    wind: Vector2D<FixedNum<8>>,
+    /// `pos` is the top left of the ball, so its centre is this far in from there
+    radius: FixedNum<8>,
+    edge_mode: EdgeMode,
+    /// The part of the screen the game's played in. The ball bounces off its top and bottom (or
+    /// wraps between them) and goes out past its ends.
+    court: Rect<FixedNum<8>>,
+    /// How far the ball's path curves each frame in radians, clockwise on the screen
+    spin: FixedNum<8>,
+    /// Steps in play since the last point, or since the watchdog last nudged the ball
+    quiet_steps: u32,
+    /// The rally when the watchdog last nudged the ball this point
+    nudged_rally: u32,
+}
+
+impl Ball {
+    pub fn new(pos: Vector2D<FixedNum<8>>, velocity: Vector2D<FixedNum<8>>) -> Self {
+        Self {
+            // The left paddle gets the first serve of each game
+            state: BallState::Held {
+                side: Side::Left,
+                held_for: 0,
+                aim_angle: num!(0),
+            },
+            pos,
+            velocity,
+            base_velocity: velocity,
+            rally: 0,
+            last_hit: None,
+            trail: [pos; TRAIL_LENGTH],
+            trail_next: 0,
+            hit_flash: 0,
+            gravity: vec2(num!(0), num!(0)),
+            wind: vec2(num!(0), num!(0)),
+            radius: BALL_RADIUS,
+            edge_mode: EdgeMode::Bounce,
+            court: full_court(),
+            spin: num!(0),
+            quiet_steps: 0,
+            nudged_rally: 0,
+        }
+    }
+
+    /// Makes the ball `LARGE_BALL_RADIUS` or the normal size
+    pub fn with_radius(mut self, radius: FixedNum<8>) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: Vector2D<FixedNum<8>>) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_edge_mode(mut self, edge_mode: EdgeMode) -> Self {
+        self.edge_mode = edge_mode;
+        self
+    }
+
+    pub fn with_court(mut self, court: Rect<FixedNum<8>>) -> Self {
+        self.court = court;
+        self
+    }
+
+    /// Sets how hard the wind is pushing the ball, until it's changed again
+    pub fn set_wind(&mut self, wind: Vector2D<FixedNum<8>>) {
+        self.wind = wind;
+    }
+
+    /// Moves the ball on by a frame, returning the side that conceded if it went off the edge of
+    /// the screen. Anything it hits along the way goes into `events`.
+    pub fn update(
+        &mut self,
+        paddle_a: &mut Paddle,
+        opponent: &mut Opponent,
+        obstacles: &[Obstacle],
+        events: &mut EventSink,
+    ) -> Option<Side> {
+        if !self.is_in_play() {
+            self.follow_server(paddle_a, opponent);
+            return None;
+        }
+        self.unstall();
+
+        self.trail[self.trail_next] = self.pos;
+        self.trail_next = (self.trail_next + 1) % TRAIL_LENGTH;
+        self.hit_flash = self.hit_flash.saturating_sub(1);
+
+        // Speculatively move the ball, we'll update the velocity if this causes it to intersect with either the
+        // edge of the map or a paddle. If it hits a paddle or obstacle part way there, it stops
+        // just short of it and bounces from there instead.
+        let (clear_pos, potential_ball_pos) = self.sweep(|mask| {
+            mask.touches(paddle_a.collision_rect())
+                || opponent.touches(mask)
+                || obstacles
+                    .iter()
+                    .any(|obstacle| mask.touches(obstacle.collision_rect()))
+        });
+        self.pos = clear_pos;
+
+        // A ball that's still overlapping a paddle after bouncing off it is already on its way
+        // out, so only count the paddle as hit while the ball is heading into it. Otherwise the
+        // ball would be knocked back in again, and could get caught bouncing inside the paddle.
+        // Paddles are only hit from the front too, as a ball clipping the back of one on its way
+        // into the goal bouncing back out would look wrong.
+        let ball_mask = Circle::new(potential_ball_pos, self.radius);
+        let heading_into = |normal: &Vector2D<FixedNum<8>>| self.velocity.dot(*normal) < num!(0);
+        let front_hit = |rect, side| {
+            ball_mask
+                .collision_normal(rect)
+                .filter(heading_into)
+                .filter(|_| from_front(self.velocity, ball_mask.centre(), rect, side))
+        };
+        let normal_a = front_hit(paddle_a.collision_rect(), Side::Left);
+        let normal_b = match opponent {
+            Opponent::Paddle(paddle_b) => front_hit(paddle_b.collision_rect(), Side::Right),
+            Opponent::Bricks(_) => None,
+        };
+        let touches_a = normal_a.is_some();
+        let touches_b = normal_b.is_some();
+        let mut hit = None;
+
+        // Scaling a moving ball can never stop it, so this is safe to combine with the angle
+        // change below
+        if touches_a || touches_b {
+            self.velocity *= BALL_SPEEDUP;
+        }
+
+        // A slow ball can end up partly inside a paddle, so push it back out of the front face
+        // first. Otherwise it could still be overlapping next frame and get knocked about again.
+        if let Some(normal) = normal_a {
+            let penetration =
+                paddle_a.collision_rect().bottom_right().x + ball_mask.radius - self.centre().x;
+            if normal.x > num!(0) && penetration > num!(0) {
+                self.pos.x += penetration;
+            }
+
+            self.bounce(normal);
+            self.deflect(
+                paddle_a.collision_rect(),
+                ball_mask.centre().y,
+                paddle_a.last_move(),
+            );
+            self.velocity *= num!(1) + paddle_a.release_shot() * MAX_CHARGE_BOOST;
+            self.last_hit = Some(Side::Left);
+            paddle_a.flash();
+            let front = vec2(paddle_a.collision_rect().bottom_right().x, self.centre().y);
+            hit = Some((Side::Left, front));
+        }
+
+        if let (Some(normal), Opponent::Paddle(paddle_b)) = (normal_b, &mut *opponent) {
+            let penetration =
+                self.centre().x + ball_mask.radius - paddle_b.collision_rect().top_left().x;
+            if normal.x < num!(0) && penetration > num!(0) {
+                self.pos.x -= penetration;
+            }
+
+            self.bounce(normal);
+            self.deflect(
+                paddle_b.collision_rect(),
+                ball_mask.centre().y,
+                paddle_b.last_move(),
+            );
+            self.velocity *= num!(1) + paddle_b.release_shot() * MAX_CHARGE_BOOST;
+            self.last_hit = Some(Side::Right);
+            paddle_b.flash();
+            let front = vec2(paddle_b.collision_rect().top_left().x, self.centre().y);
+            hit = Some((Side::Right, front));
+        }
+
+        // Only count one hit per frame, even if the ball somehow touched both paddles. Clamping
+        // the speed here also stops the spin from pushing the ball past the speed limit.
+        if let Some((side, pos)) = hit {
+            self.rally += 1;
+            self.hit_flash = HIT_FLASH_FRAMES;
+            self.clamp_angle();
+            self.clamp_speed();
+
+            events.push(GameEvent::PaddleHit {
+                side,
+                pos,
+                speed: self.speed_fraction(),
+            });
+        }
+
+        // Obstacles bounce the ball like the walls do, so they don't count towards the rally. A
+        // moving one pushes the ball along a bit too, so it doesn't feel dead.
+        for obstacle in obstacles {
+            if let Some(normal) = ball_mask.collision_normal(obstacle.collision_rect()) {
+                self.bounce(normal);
+                if obstacle.velocity() != vec2(num!(0), num!(0)) {
+                    self.velocity += obstacle.velocity() * OBSTACLE_SPIN;
+                    self.clamp_angle();
+                    self.clamp_speed();
+                }
+                events.push(GameEvent::Bounce {
+                    pos: ball_mask.centre() - normal * ball_mask.radius,
+                    normal,
+                });
+            }
+        }
+
+        if let Opponent::Bricks(bricks) = opponent {
+            self.hit_bricks(&ball_mask, bricks, events);
+        }
+
+        // We check if the ball reaches the edge of the screen and send it back the other way.
+        // Gravity can pull a slow ball further into the wall before the bounce takes effect, so
+        // the y speed is set away from the wall rather than just flipped.
+        let (near, far) = self.travel();
+        if self.edge_mode == EdgeMode::Bounce && self.touches_wall(potential_ball_pos) {
+            let off_top = potential_ball_pos.y <= near.y;
+            self.velocity.y = if off_top {
+                self.velocity.y.abs()
+            } else {
+                -self.velocity.y.abs()
+            };
+            let (y, normal) = if off_top {
+                (self.court.top_left().y, vec2(num!(0), num!(1)))
+            } else {
+                (self.court.bottom_right().y, vec2(num!(0), num!(-1)))
+            };
+            events.push(GameEvent::WallHit {
+                pos: vec2(self.centre().x, y),
+                normal,
+                speed: self.speed_fraction(),
+            });
+        }
+
+        if potential_ball_pos.x <= near.x {
+            paddle_a.lose_life();
+            if let Opponent::Paddle(paddle_b) = opponent {
+                paddle_b.add_point();
+            }
+            events.push(GameEvent::Score { side: Side::Left });
+            return Some(Side::Left);
+        }
+        if potential_ball_pos.x >= far.x {
+            match opponent {
+                Opponent::Paddle(paddle_b) => {
+                    paddle_b.lose_life();
+                    paddle_a.add_point();
+                    events.push(GameEvent::Score { side: Side::Right });
+                    return Some(Side::Right);
+                }
+                Opponent::Bricks(_) => {
+                    self.velocity.x = -self.velocity.x.abs();
+                    events.push(GameEvent::Bounce {
+                        pos: vec2(self.court.bottom_right().x, self.centre().y),
+                        normal: vec2(num!(-1), num!(0)),
+                    });
+                }
+            }
+        }
+
+        if self.spin != num!(0) {
+            self.velocity = rotate(self.velocity, self.spin);
+            self.spin = decay_spin(self.spin);
+            self.clamp_angle();
+        }
+
+        // Gravity keeps adding speed, so this needs capping every frame rather than just on hits
+        let acceleration = self.gravity + self.wind;
+        if acceleration != vec2(num!(0), num!(0)) {
+            let x_direction = self.velocity.x;
+            self.velocity += acceleration;
+            self.clamp_speed();
+
+            // A headwind can slow the ball down, but it would be unfair for it to blow the ball
+            // back towards whoever just hit it
+            let min_x_speed = self.min_x_speed();
+            if self.wind.x != num!(0)
+                && (self.velocity.x * x_direction <= num!(0) || self.velocity.x.abs() < min_x_speed)
+            {
+                self.velocity.x = if x_direction < num!(0) {
+                    -min_x_speed
+                } else {
+                    min_x_speed
+                };
+            }
+        }
+
+        self.pos += self.velocity;
+
+        // The trail moves round with the ball, otherwise it would streak right across the screen
+        let shift = self.wrap_shift(self.pos.y);
+        if shift != num!(0) {
+            self.pos.y += shift;
+            for pos in &mut self.trail {
+                pos.y += shift;
+            }
+        }
+        None
+    }
+
+    /// Moves the ball on by a frame in a four player game, where every edge of the screen is
+    /// guarded by one of `paddles`. Returns the side that conceded if the ball got past its
+    /// paddle. A side that's been knocked out is walled off instead, and the ball bounces back.
+    ///
+    /// There are no top and bottom walls to keep the ball off, so unlike [`Ball::update`] the
+    /// angle is left alone, and there are no obstacles, gravity or wind.
+    pub fn update_four_walls(
+        &mut self,
+        paddles: &mut [Paddle; 4],
+        events: &mut EventSink,
+    ) -> Option<Side> {
+        if !self.is_in_play() {
+            return None;
+        }
+        self.unstall();
+
+        self.trail[self.trail_next] = self.pos;
+        self.trail_next = (self.trail_next + 1) % TRAIL_LENGTH;
+        self.hit_flash = self.hit_flash.saturating_sub(1);
+
+        let (clear_pos, potential_ball_pos) = self.sweep(|mask| {
+            paddles
+                .iter()
+                .any(|paddle| paddle.health() > 0 && mask.touches(paddle.collision_rect()))
+        });
+        self.pos = clear_pos;
+
+        // Paddles are only hit from the front on the way in, the same as in a two player game,
+        // and only one of them can be hit in a frame
+        let ball_mask = Circle::new(potential_ball_pos, self.radius);
+        let velocity = self.velocity;
+        let hit = paddles
+            .iter_mut()
+            .filter(|paddle| paddle.health() > 0)
+            .find_map(|paddle| {
+                let rect = paddle.collision_rect();
+                ball_mask
+                    .collision_normal(rect)
+                    .filter(|normal| velocity.dot(*normal) < num!(0))
+                    .filter(|_| from_front(velocity, ball_mask.centre(), rect, paddle.side()))
+                    .map(|normal| (paddle, normal))
+            });
+
+        if let Some((paddle, normal)) = hit {
+            let rect = paddle.collision_rect();
+            let side = paddle.side();
+
+            self.velocity *= BALL_SPEEDUP;
+            self.bounce(normal);
+            if side.is_horizontal() {
+                self.deflect_across(rect, ball_mask.centre().x, paddle.last_move());
+            } else {
+                self.deflect(rect, ball_mask.centre().y, paddle.last_move());
+            }
+            self.velocity *= num!(1) + paddle.release_shot() * MAX_CHARGE_BOOST;
+            self.last_hit = Some(side);
+            paddle.flash();
+
+            self.rally += 1;
+            self.hit_flash = HIT_FLASH_FRAMES;
+            self.clamp_speed();
+            events.push(GameEvent::PaddleHit {
+                side,
+                pos: ball_mask.centre() + side.outward() * self.radius,
+                speed: self.speed_fraction(),
+            });
+        }
+
+        let (near, far) = self.travel();
+        for paddle in paddles.iter_mut() {
+            let side = paddle.side();
+            let out = match side {
+                Side::Left => potential_ball_pos.x <= near.x,
+                Side::Right => potential_ball_pos.x >= far.x,
+                Side::Top => potential_ball_pos.y <= near.y,
+                Side::Bottom => potential_ball_pos.y >= far.y,
+            };
+            if !out {
+                continue;
+            }
+
+            if paddle.health() > 0 {
+                paddle.lose_life();
+                events.push(GameEvent::Score { side });
+                return Some(side);
+            }
+
+            let normal = side.opposite().outward();
+            self.bounce(normal);
+            events.push(GameEvent::Bounce {
+                pos: ball_mask.centre() - normal * self.radius,
+                normal,
+            });
+        }
+
+        if self.spin != num!(0) {
+            self.velocity = rotate(self.velocity, self.spin);
+            self.spin = decay_spin(self.spin);
+        }
+
+        self.pos += self.velocity;
+        None
+    }
+
+    /// Gets a ball that's somehow come to a stop going again at its base speed, straight on
+    /// towards whoever didn't hit it last, or the nearer end if nobody has yet
+    fn unstall(&mut self) {
+        if self.velocity.magnitude_squared() >= STALL_SPEED * STALL_SPEED {
+            return;
+        }
+
+        let toward = match self.last_hit {
+            Some(side) => side.opposite(),
+            None if self.centre().x < self.court.centre().x => Side::Left,
+            None => Side::Right,
+        };
+        self.velocity = toward.outward() * self.base_velocity.magnitude();
+    }
+
+    /// How far to move a ball at `y` to wrap it round to the other edge of the court, once its
+    /// centre has gone off the top or bottom. Always 0 unless the edges wrap.
+    fn wrap_shift(&self, y: FixedNum<8>) -> FixedNum<8> {
+        let height = self.court.size.y;
+        let centre_y = y + self.radius;
+        match self.edge_mode {
+            EdgeMode::Wrap if centre_y < self.court.top_left().y => height,
+            EdgeMode::Wrap if centre_y >= self.court.bottom_right().y => -height,
+            EdgeMode::Wrap | EdgeMode::Bounce => num!(0),
+        }
+    }
+
+    /// Bounces the ball off any bricks it's touching, knocking them down a hit each
+    fn hit_bricks(
+        &mut self,
+        ball_mask: &Circle<FixedNum<8>>,
+        bricks: &mut Vec<Brick>,
+        events: &mut EventSink,
+    ) {
+        for brick in bricks.iter_mut() {
+            let Some(normal) = ball_mask.collision_normal(brick.collision_rect()) else {
+                continue;
+            };
+
+            self.bounce(normal);
+            brick.hit();
+            events.push(GameEvent::BrickHit {
+                pos: ball_mask.centre() - normal * ball_mask.radius,
+                normal,
+                speed: self.speed_fraction(),
+            });
+        }
+
+        bricks.retain(|brick| !brick.is_broken());
+    }
+
+    /// Reflects the ball's velocity about `normal`, so hits on the front of a paddle bounce it
+    /// straight back while hits on the ends glance off. A ball already moving away from the
+    /// surface is left alone, so it can't get turned back into it.
+    fn bounce(&mut self, normal: Vector2D<FixedNum<8>>) {
+        let towards = self.velocity.dot(normal);
+        if towards < num!(0) {
+            self.velocity -= normal * (towards * 2);
+        }
+    }
+
+    /// Steps along this frame's movement looking for the first point where the ball `touches` a
+    /// paddle or obstacle. Returns the last position before that along with the touching one, or
+    /// the current and next positions if it doesn't hit anything.
+    fn sweep(
+        &self,
+        touches: impl Fn(&Circle<FixedNum<8>>) -> bool,
+    ) -> (Vector2D<FixedNum<8>>, Vector2D<FixedNum<8>>) {
+        let distance = self.velocity.x.abs().max(self.velocity.y.abs());
+        let steps = (distance / SWEEP_STEP).floor() + 1;
+
+        let mut clear_pos = self.pos;
+        for step in 1..=steps {
+            let pos = self.pos + self.velocity * step / steps;
+            if touches(&Circle::new(pos, self.radius)) {
+                return (clear_pos, pos);
+            }
+            clear_pos = pos;
+        }
+
+        (self.pos, self.pos + self.velocity)
+    }
+
+    /// Puts the ball where the host of a link game says it is, leaving a trail behind it as if it
+    /// had moved there itself
+    pub fn sync(&mut self, pos: Vector2D<FixedNum<8>>, velocity: Vector2D<FixedNum<8>>) {
+        self.trail[self.trail_next] = self.pos;
+        self.trail_next = (self.trail_next + 1) % TRAIL_LENGTH;
+
+        self.state = BallState::InPlay;
+        self.pos = pos;
+        self.velocity = velocity;
+    }
+
+    /// Puts the ball back how it was in a saved match, held ready to serve by `server` or in
+    /// play if that's None. A held ball aims the same way it was going to be served.
+    pub fn resume(
+        &mut self,
+        pos: Vector2D<FixedNum<8>>,
+        velocity: Vector2D<FixedNum<8>>,
+        rally: u32,
+        server: Option<Side>,
+    ) {
+        self.pos = pos;
+        self.velocity = velocity;
+        self.rally = rally;
+        self.trail = [pos; TRAIL_LENGTH];
+        self.state = match server {
+            Some(side) => BallState::Held {
+                side,
+                held_for: 0,
+                aim_angle: if velocity.x == num!(0) {
+                    num!(0)
+                } else {
+                    (velocity.y / velocity.x.abs()).clamp(-MAX_SERVE_ANGLE, MAX_SERVE_ANGLE)
+                },
+            },
+            None => BallState::InPlay,
+        };
+    }
+
+    /// Which paddle is holding on to the ball ready to serve, or None once it's in play
+    pub fn server(&self) -> Option<Side> {
+        match self.state {
+            BallState::Held { side, .. } => Some(side),
+            BallState::InPlay => None,
+        }
+    }
+
+    pub fn pos(&self) -> Vector2D<FixedNum<8>> {
+        self.pos
+    }
+
+    pub fn velocity(&self) -> Vector2D<FixedNum<8>> {
+        self.velocity
+    }
+
+    /// How fast the ball's going in pixels per frame, whichever way that is
+    pub fn speed(&self) -> FixedNum<8> {
+        self.velocity.magnitude()
+    }
+
+    pub fn rally(&self) -> u32 {
+        self.rally
+    }
+
+    pub fn last_hit(&self) -> Option<Side> {
+        self.last_hit
+    }
+
+    pub fn radius(&self) -> FixedNum<8> {
+        self.radius
+    }
+
+    pub fn collision_mask(&self) -> Circle<FixedNum<8>> {
+        Circle::new(self.pos, self.radius)
+    }
+
+    fn centre(&self) -> Vector2D<FixedNum<8>> {
+        self.pos + vec2(self.radius, self.radius)
+    }
+
+    /// Sends the ball away from a paddle hit at an angle picked by where on `paddle` it landed,
+    /// keeping its speed. The angle ramps up slowly near the middle and quickly towards the ends,
+    /// so the ends are for steep shots and most of the paddle sends it fairly flat.
+    ///
+    /// The ball is also nudged along by `paddle_move`, and picks up spin from the ends and from
+    /// the paddle's movement, which carries on curving it the way the paddle was moving.
+    fn deflect(
+        &mut self,
+        paddle: Rect<FixedNum<8>>,
+        centre_y: FixedNum<8>,
+        paddle_move: FixedNum<8>,
+    ) {
+        // From -1 for a ball only just clipping the top end to 1 at the bottom. A larger ball can
+        // land further off centre while still touching, so its reach is counted too.
+        let reach = paddle.size.y / 2 + self.radius;
+        let offset = ((centre_y - paddle.centre().y) / reach)
+            .max(num!(-1))
+            .min(num!(1));
+        let slope = MAX_DEFLECTION * offset * offset.abs();
+
+        let speed = self.velocity.magnitude();
+        let x_direction = if self.velocity.x < num!(0) {
+            num!(-1)
+        } else {
+            num!(1)
+        };
+        self.velocity = vec2(x_direction, slope).normalise() * speed;
+        self.velocity.y += paddle_move * PADDLE_NUDGE;
+        // Clockwise turns a ball heading right downwards, but one heading left upwards
+        self.spin = (paddle_move * PADDLE_SPIN + offset * OFFSET_SPIN) * x_direction;
+    }
+
+    /// Like [`Ball::deflect`], but for a paddle lying along the top or bottom of the screen.
+    /// Swapping x and y over turns it into a paddle at one end, and swapping them back afterwards
+    /// mirrors the court, so the spin needs to turn the other way.
+    fn deflect_across(
+        &mut self,
+        paddle: Rect<FixedNum<8>>,
+        centre_x: FixedNum<8>,
+        paddle_move: FixedNum<8>,
+    ) {
+        let swap = |v: Vector2D<FixedNum<8>>| vec2(v.y, v.x);
+        self.velocity = swap(self.velocity);
+        self.deflect(
+            Rect::new(swap(paddle.position), swap(paddle.size)),
+            centre_x,
+            paddle_move,
+        );
+        self.velocity = swap(self.velocity);
+        self.spin = -self.spin;
+    }
+
+    /// Keeps a held ball just in front of the serving paddle, not quite touching it
+    pub fn follow_server(&mut self, paddle_a: &Paddle, opponent: &Opponent) {
+        let BallState::Held { side, .. } = self.state else {
+            return;
+        };
+
+        // Bricks can't serve, so against them it's always the player's serve
+        let (side, rect) = match (side, opponent) {
+            (Side::Right, Opponent::Paddle(paddle_b)) => (side, paddle_b.collision_rect()),
+            _ => (Side::Left, paddle_a.collision_rect()),
+        };
+        // Out from the middle of the paddle's front face by the ball's radius and a pixel more
+        let inward = side.opposite().outward();
+        let reach = rect.size / 2 + vec2(self.radius, self.radius) + vec2(num!(1), num!(1));
+        let centre = rect.centre() + vec2(inward.x * reach.x, inward.y * reach.y);
+        self.pos = centre - vec2(self.radius, self.radius);
+
+        // A held ball shouldn't leave a trail, or it would be left streaking out of the goal
+        self.trail = [self.pos; TRAIL_LENGTH];
+    }
+
+    /// How close the ball is to its top speed, going by the squared speed to save a sqrt. Since
+    /// the speed is capped this is between 0 and 1.
+    fn speed_fraction(&self) -> FixedNum<8> {
+        let max_speed = self.max_speed();
+        self.velocity.magnitude_squared() / (max_speed * max_speed)
+    }
+
+    /// The fastest the ball's allowed to go, in pixels per frame
+    fn max_speed(&self) -> FixedNum<8> {
+        self.base_velocity.magnitude() * MAX_SPEEDUP
+    }
+
+    fn min_x_speed(&self) -> FixedNum<8> {
+        self.base_velocity.magnitude() * MIN_X_SPEED
+    }
+
+    fn clamp_angle(&mut self) {
+        let x_speed = self.velocity.x.abs();
+        let y_speed = self.velocity.y.abs();
+
+        let clamped_y_speed = y_speed
+            .max(x_speed * MIN_BALL_ANGLE)
+            .min(x_speed * MAX_BALL_ANGLE);
+        if x_speed == num!(0) || clamped_y_speed == y_speed {
+            return;
+        }
+
+        // Changing the angle shouldn't change how fast the ball is going
+        let speed = self.velocity.magnitude();
+        let y_speed = if self.velocity.y < num!(0) {
+            -clamped_y_speed
+        } else {
+            clamped_y_speed
+        };
+        self.velocity = vec2(self.velocity.x, y_speed).normalise() * speed;
+    }
+
+    fn clamp_speed(&mut self) {
+        // Comparing the squared speed means we only need the sqrt when the ball is actually too fast
+        let max_speed = self.max_speed();
+        if self.velocity.magnitude_squared() <= max_speed * max_speed {
+            return;
+        }
+
+        let x_direction = self.velocity.x;
+        self.velocity = self.velocity.normalise() * max_speed;
+
+        // A very steep ball could end up with almost no horizontal speed, which would leave it
+        // bouncing between the top and bottom walls forever
+        let min_x_speed = self.min_x_speed();
+        if self.velocity.x.abs() < min_x_speed {
+            self.velocity.x = if x_direction < num!(0) {
+                -min_x_speed
+            } else {
+                min_x_speed
+            };
+        }
+    }
+
+    /// The range of positions the ball can be at while it's all inside the court. `pos` is the
+    /// top left of the ball, so the far end is a ball's width in from the court's edges.
+    fn travel(&self) -> (Vector2D<FixedNum<8>>, Vector2D<FixedNum<8>>) {
+        let diameter = self.radius * 2;
+        (
+            self.court.top_left(),
+            self.court.bottom_right() - vec2(diameter, diameter),
+        )
+    }
+
+    fn touches_wall(&self, pos: Vector2D<FixedNum<8>>) -> bool {
+        let (near, far) = self.travel();
+        pos.y <= near.y || pos.y >= far.y
+    }
+
+    /// Works out the x position the ball will be at once it reaches `target_y`, for the paddles
+    /// along the top and bottom in a four player game. There are no walls to bounce off on the
+    /// way there, and like [`Ball::predict_y_at_x`] it's just the current x position if the ball
+    /// is heading away.
+    pub fn predict_x_at_y(&self, target_y: FixedNum<8>) -> FixedNum<8> {
+        let mut pos = self.pos;
+        let mut velocity = self.velocity;
+        let mut spin = self.spin;
+
+        while (target_y - pos.y) * velocity.y > num!(0) {
+            if spin != num!(0) {
+                velocity = rotate(velocity, spin);
+                spin = decay_spin(spin);
+            }
+            pos += velocity;
+        }
+
+        pos.x
+    }
+
+    /// Works out the y position the ball will be at once it reaches `target_x`, bouncing off the
+    /// top and bottom walls on the way. If the ball is heading away from `target_x`, this is just
+    /// the current y position.
+    pub fn predict_y_at_x(&self, target_x: FixedNum<8>) -> FixedNum<8> {
+        let mut pos = self.pos;
+        let mut velocity = self.velocity;
+        let mut spin = self.spin;
+
+        // Step the ball forward the same way `update` does, ignoring the paddles. The loop stops
+        // immediately if the ball is moving away, and otherwise ends once it passes `target_x`.
+        while (target_x - pos.x) * velocity.x > num!(0) {
+            if self.edge_mode == EdgeMode::Bounce && self.touches_wall(pos + velocity) {
+                velocity.y = if pos.y + velocity.y <= self.court.top_left().y {
+                    velocity.y.abs()
+                } else {
+                    -velocity.y.abs()
+                };
+            }
+            if spin != num!(0) {
+                velocity = rotate(velocity, spin);
+                spin = decay_spin(spin);
+            }
+            velocity.y += self.gravity.y;
+            pos += velocity;
+            pos.y += self.wrap_shift(pos.y);
+        }
+
+        pos.y
+    }
+
+    /// Sets up the next serve towards `toward`, normally the side that just lost the point. The
+    /// other paddle holds on to the ball until it's served. The angle is picked by `rng` so that
+    /// every serve isn't the same, but the ball always starts at its base speed.
+    pub fn serve(&mut self, rng: &mut Rng, toward: Side) {
+        self.pos = self.court.top_left() + vec2(num!(50), num!(50));
+        self.rally = 0;
+        self.last_hit = None;
+        self.spin = num!(0);
+        self.quiet_steps = 0;
+        self.nudged_rally = 0;
+
+        let slope = rng.range(MIN_BALL_ANGLE, MAX_SERVE_ANGLE);
+        let slope = if rng.next_u32() & 1 == 0 {
+            slope
+        } else {
+            -slope
+        };
+
+        self.aim(toward, slope);
+        self.state = BallState::Held {
+            side: toward.opposite(),
+            held_for: 0,
+            aim_angle: slope,
+        };
+    }
+
+    /// Points the ball towards `toward` at its base speed, tipped by `slope`
+    fn aim(&mut self, toward: Side, slope: FixedNum<8>) {
+        // Sideways to the way out, so the slope tips the serve up or down, or left or right
+        let outward = toward.outward();
+        let across = vec2(outward.y.abs(), outward.x.abs()) * slope;
+        self.velocity = (outward + across).normalise() * self.base_velocity.magnitude();
+    }
+
+    /// Sets up a serve from the middle of the court towards `toward`, for a four player game
+    /// where there's nobody to hold the ball. It goes once it's launched.
+    pub fn serve_from_middle(&mut self, rng: &mut Rng, toward: Side) {
+        self.serve(rng, toward);
+        self.pos = self.court.centre() - vec2(self.radius, self.radius);
+        self.trail = [self.pos; TRAIL_LENGTH];
+    }
+
+    /// Serves a held ball when the player presses A, aimed up or down while they hold the
+    /// D-pad. The right hand paddle serves automatically after a short wait, since its A and B
+    /// buttons are taken up by movement in shared controller practice, and so does the left one
+    /// if `left_is_cpu`.
+    pub fn update_serve(&mut self, input: &Input, left_is_cpu: bool) {
+        let BallState::Held {
+            side,
+            held_for,
+            aim_angle,
+        } = &mut self.state
+        else {
+            return;
+        };
+
+        *held_for += 1;
+        let player = *side == Side::Left && !left_is_cpu;
+        let serve = if player {
+            input.is_just_pressed(Button::A)
+        } else {
+            *held_for >= CPU_SERVE_DELAY
+        };
+
+        // The paddle moves with the D-pad too, so the serve tips the way the paddle's going
+        if player {
+            let steer = match (input.is_pressed(Button::UP), input.is_pressed(Button::DOWN)) {
+                (true, false) => -AIM_SPEED,
+                (false, true) => AIM_SPEED,
+                (false, false) | (true, true) => num!(0),
+            };
+            *aim_angle = (*aim_angle + steer).clamp(-MAX_SERVE_ANGLE, MAX_SERVE_ANGLE);
+            let (toward, slope) = (side.opposite(), *aim_angle);
+            self.aim(toward, slope);
+        }
+
+        if serve {
+            self.launch();
+        }
+    }
+
+    /// Sets the ball moving with its current velocity
+    pub fn launch(&mut self) {
+        self.state = BallState::InPlay;
+    }
+
+    /// Knocks the ball out of a rally that looks like it's never going to end, turning it a
+    /// little either way with `rng` so no bounce can repeat forever. Called once a step, it
+    /// returns true on the step it had to.
+    pub fn watchdog(&mut self, rng: &mut Rng) -> bool {
+        if !self.is_in_play() {
+            return false;
+        }
+
+        self.quiet_steps += 1;
+        if self.quiet_steps < WATCHDOG_STEPS && self.rally < self.nudged_rally + WATCHDOG_RALLY {
+            return false;
+        }
+
+        self.quiet_steps = 0;
+        self.nudged_rally = self.rally;
+        let (min, max) = WATCHDOG_NUDGE;
+        let angle = rng.range(min, max);
+        let angle = if rng.next_u32() & 1 == 0 {
+            angle
+        } else {
+            -angle
+        };
+        self.velocity = rotate(self.velocity, angle);
+        // The nudge can't be allowed to turn a steep ball steeper than any other shot could go
+        self.clamp_angle();
+        true
+    }
+
+    /// Takes away any speedup the ball has built up, keeping the direction it's going in
+    pub fn slow_down(&mut self) {
+        let base_speed = self.base_velocity.magnitude();
+        if self.velocity.magnitude_squared() > base_speed * base_speed {
+            self.velocity = self.velocity.normalise() * base_speed;
+        }
+    }
+
+    pub fn is_in_play(&self) -> bool {
+        self.state == BallState::InPlay
+    }
+
+    /// Shows the ball, and hands its trail to `budget` to be shown once there's room
+    pub fn show(&self, budget: &mut SpriteBudget) {
+        // Rounded to the nearest pixel like the paddles, which drop their slack with the ball close
+        // by, so a ball touching one is drawn touching it. It only turns around when it bounces,
+        // so it doesn't need the slack itself.
+        let pos = self.pos.round();
+        let large = self.radius > BALL_RADIUS;
+        let sprite = match (large, self.hit_flash > 0) {
+            (false, false) => sprites::BALL.sprite(0),
+            (false, true) => sprites::BALL_FLASH.sprite(0),
+            (true, false) => sprites::BIG_BALL.sprite(0),
+            (true, true) => sprites::BIG_BALL_FLASH.sprite(0),
+        };
+        budget.show(Object::new(sprite).set_pos(pos).set_priority(Priority::P1));
+        self.show_aim(budget);
+
+        // Each ghost is smaller and dimmer than the last, and drawn behind the balls and paddles.
+        // They're all sized for the normal ball, so they're moved to line up with a larger one.
+        let trail_offset = vec2(self.radius - BALL_RADIUS, self.radius - BALL_RADIUS);
+        for i in 0..TRAIL_LENGTH {
+            let newest_first = (self.trail_next + TRAIL_LENGTH - 1 - i) % TRAIL_LENGTH;
+            let mut ghost = Object::new(sprites::BALL_TRAIL.sprite(i));
+            ghost
+                .set_pos((self.trail[newest_first] + trail_offset).round())
+                .set_priority(Priority::P2);
+            budget.decorate(Decoration::Trail(i), ghost);
+        }
+    }
+
+    /// Points an arrow out in front of a held ball, the way it's going to be served
+    fn show_aim(&self, frame: &mut impl ObjectSink) {
+        let BallState::Held {
+            side, aim_angle, ..
+        } = self.state
+        else {
+            return;
+        };
+        // Four player serves aren't held by anybody, they just wait in the middle
+        if side.is_horizontal() {
+            return;
+        }
+
+        // The arrows are drawn pointing right from the steepest serve up to the steepest down
+        let steps = sprites::AIM.sprites().len() as i32 - 1;
+        let index = ((aim_angle + MAX_SERVE_ANGLE) * steps / (MAX_SERVE_ANGLE * 2)).round();
+        let centre = self.centre() + self.velocity.normalise() * (self.radius + num!(10));
+        frame.show(
+            Object::new(sprites::AIM.sprite(index.clamp(0, steps) as usize))
+                .set_pos(centre.round() - vec2(8, 8))
+                .set_hflip(side == Side::Right)
+                .set_priority(Priority::P1),
+        );
+    }
+}
+
+/// Turns `velocity` by `angle` radians, clockwise on the screen. The angles are small enough to
+/// use sin θ ≈ θ and cos θ ≈ 1 - θ²/2 instead of any trig, which keeps the speed the same well
+/// within rounding. It's worked out at a higher precision since θ² is too small to show up in 8
+/// fractional bits.
+fn rotate(velocity: Vector2D<FixedNum<8>>, angle: FixedNum<8>) -> Vector2D<FixedNum<8>> {
+    let angle: FixedNum<16> = angle.change_base();
+    let x: FixedNum<16> = velocity.x.change_base();
+    let y: FixedNum<16> = velocity.y.change_base();
+    let cos = num!(1) - angle * angle / 2;
+
+    vec2(
+        (x * cos - y * angle).change_base(),
+        (y * cos + x * angle).change_base(),
+    )
+}
+
+/// Takes `SPIN_DECAY` off `spin` towards zero, stopping at zero rather than going past it
+fn decay_spin(spin: FixedNum<8>) -> FixedNum<8> {
+    if spin.abs() <= SPIN_DECAY {
+        num!(0)
+    } else if spin < num!(0) {
+        spin + SPIN_DECAY
+    } else {
+        spin - SPIN_DECAY
+    }
+}
+
+/// True if a ball at `centre` moving at `velocity` is coming at the paddle `rect` from in front,
+/// where the paddle on `side` faces into the court
+fn from_front(
+    velocity: Vector2D<FixedNum<8>>,
+    centre: Vector2D<FixedNum<8>>,
+    rect: Rect<FixedNum<8>>,
+    side: Side,
+) -> bool {
+    let outward = side.outward();
+    velocity.dot(outward) > num!(0) && (centre - rect.centre()).dot(outward) < num!(0)
+}
+
+/// Bounces every pair of touching balls in play off each other. They all weigh the same, so the
+/// collision just swaps the parts of their velocities along the line between their centres. Then
+/// they're pushed apart so they don't stick together. There's never more than a few balls, so
+/// checking every pair is cheap enough.
+pub fn collide_balls(balls: &mut [Ball], events: &mut EventSink) {
+    for i in 1..balls.len() {
+        let (before, rest) = balls.split_at_mut(i);
+        let b = &mut rest[0];
+        for a in before.iter_mut() {
+            if a.is_in_play() && b.is_in_play() && a.collision_mask().touches(b.collision_mask()) {
+                collide(a, b, events);
+            }
+        }
+    }
+}
+
+fn collide(a: &mut Ball, b: &mut Ball, events: &mut EventSink) {
+    let offset = b.centre() - a.centre();
+    let distance = offset.magnitude();
+    // Balls right on top of each other, like two served from the same spot, have no line
+    // between them, so they're split up and down which doesn't send either at a paddle
+    let normal = if distance == num!(0) {
+        vec2(num!(0), num!(1))
+    } else {
+        offset / distance
+    };
+
+    let overlap = a.radius + b.radius - distance;
+    if overlap > num!(0) {
+        let push = normal * (overlap / 2);
+        a.pos -= push;
+        b.pos += push;
+    }
+
+    // Only swap if they're coming together. Balls that are overlapping but already moving apart
+    // are just separated, so overlapping balls push apart rather than fly off at speed. Being
+    // pushed apart leaves them just touching, so balls travelling side by side touch every frame
+    // and that mustn't count as a bounce.
+    let closing = (a.velocity - b.velocity).dot(normal);
+    if closing > num!(0) {
+        a.velocity -= normal * closing;
+        b.velocity += normal * closing;
+        events.push(GameEvent::Bounce {
+            pos: a.centre() + normal * a.radius,
+            normal: -normal,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paddle::start_pos;
+
+    #[test_case]
+    fn both_ends_of_the_court_are_the_same(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, start_pos(Side::Left), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, start_pos(Side::Right), num!(2), 3);
+
+        let width = num!(agb::display::WIDTH);
+        let (a, b) = (paddle_a.collision_rect(), paddle_b.collision_rect());
+        assert_eq!(a.top_left().x, width - b.bottom_right().x);
+        assert_eq!(a.bottom_right().x, width - b.top_left().x);
+
+        // Served flat from the middle, under the paddles so they're out of the way
+        let mut frames = [0; 2];
+        for (taken, x_speed) in frames.iter_mut().zip([num!(-2), num!(2)]) {
+            let centre = vec2(width / 2, num!(agb::display::HEIGHT - 24));
+            let mut ball = Ball::new(
+                centre - vec2(BALL_RADIUS, BALL_RADIUS),
+                vec2(x_speed, num!(0)),
+            );
+            ball.launch();
+
+            *taken = (1..1000)
+                .find(|_| {
+                    let mut opponent = Opponent::Paddle(&mut paddle_b);
+                    ball.update(&mut paddle_a, &mut opponent, &[], &mut events)
+                        .is_some()
+                })
+                .expect("the ball should reach the edge");
+        }
+        assert_eq!(
+            frames[0], frames[1],
+            "both sides should take as long to score on"
+        );
+    }
+
+    #[test_case]
+    fn stopped_ball_gets_going_again(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, start_pos(Side::Left), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, start_pos(Side::Right), num!(2), 3);
+
+        let mut ball = Ball::new(vec2(num!(150), num!(100)), vec2(num!(2), num!(0)));
+        ball.launch();
+        ball.last_hit = Some(Side::Right);
+        ball.velocity = vec2(num!(0), num!(0));
+        let start = ball.pos;
+
+        ball.update(
+            &mut paddle_a,
+            &mut Opponent::Paddle(&mut paddle_b),
+            &[],
+            &mut events,
+        );
+        assert_eq!(ball.velocity, vec2(num!(-2), num!(0)));
+        assert!(ball.pos.x < start.x, "the ball should be on its way again");
+    }
+
+    #[test_case]
+    fn slow_ball_inside_paddle_escapes(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, start_pos(Side::Left), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, start_pos(Side::Right), num!(2), 3);
+
+        // Half way into the front of the left paddle and still drifting into it
+        let mut ball = Ball::new(vec2(num!(10), num!(20)), vec2(num!(-0.25), num!(0)));
+        ball.state = BallState::InPlay;
+
+        for _ in 0..10 {
+            ball.update(
+                &mut paddle_a,
+                &mut Opponent::Paddle(&mut paddle_b),
+                &[],
+                &mut events,
+            );
+
+            assert!(
+                ball.velocity.x > num!(0),
+                "ball should head away from the paddle"
+            );
+            assert!(
+                !ball.collision_mask().touches(paddle_a.collision_rect()),
+                "ball should be clear of the paddle"
+            );
+        }
+
+        assert_eq!(ball.rally, 1, "the ball should only bounce off once");
+    }
+
+    #[test_case]
+    fn ball_overlapping_a_paddle_only_bounces_off_once(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, vec2(num!(8), num!(40)), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, start_pos(Side::Right), num!(2), 3);
+
+        // Dropping onto the top of the left paddle, which doesn't push the ball back out, so it
+        // stays overlapping for a few frames after the bounce
+        let mut ball = Ball::new(vec2(num!(10), num!(30)), vec2(num!(-0.5), num!(0.5)));
+        ball.state = BallState::InPlay;
+
+        let mut overlapping_frames = 0;
+        for _ in 0..10 {
+            ball.update(
+                &mut paddle_a,
+                &mut Opponent::Paddle(&mut paddle_b),
+                &[],
+                &mut events,
+            );
+            if ball.collision_mask().touches(paddle_a.collision_rect()) {
+                overlapping_frames += 1;
+            }
+        }
+
+        assert!(overlapping_frames > 1, "the ball should stay overlapping");
+        assert_eq!(ball.rally, 1, "the ball should only bounce off once");
+        assert!(ball.velocity.y < num!(0), "the ball should head back up");
+    }
+
+    #[test_case]
+    fn ball_passes_through_the_back_of_a_paddle(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        // Far enough from the edges to get behind them without going into the goal
+        let mut paddle_a = Paddle::new(Side::Left, vec2(num!(60), num!(40)), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, vec2(num!(164), num!(40)), num!(2), 3);
+
+        for (pos, velocity) in [
+            (vec2(num!(40), num!(56)), vec2(num!(2), num!(0))),
+            (vec2(num!(184), num!(56)), vec2(num!(-2), num!(0))),
+        ] {
+            let mut ball = Ball::new(pos, velocity);
+            ball.state = BallState::InPlay;
+
+            for _ in 0..20 {
+                ball.update(
+                    &mut paddle_a,
+                    &mut Opponent::Paddle(&mut paddle_b),
+                    &[],
+                    &mut events,
+                );
+            }
+
+            assert_eq!(ball.velocity, velocity, "the ball shouldn't be reflected");
+            assert_eq!(ball.rally, 0);
+        }
+    }
+
+    #[test_case]
+    fn ball_bounces_off_the_top_wall(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, vec2(num!(-100), num!(8)), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, vec2(num!(400), num!(8)), num!(2), 3);
+
+        let mut ball = Ball::new(vec2(num!(100), num!(2)), vec2(num!(1), num!(-3)));
+        ball.state = BallState::InPlay;
+
+        ball.update(
+            &mut paddle_a,
+            &mut Opponent::Paddle(&mut paddle_b),
+            &[],
+            &mut events,
+        );
+
+        assert_eq!(ball.velocity, vec2(num!(1), num!(3)));
+        assert!(
+            events
+                .iter()
+                .all(|event| matches!(event, GameEvent::WallHit { .. })),
+            "only the wall should have been hit"
+        );
+        assert_eq!(events.iter().count(), 1);
+    }
+
+    #[test_case]
+    fn paddle_hits_angle_the_ball_away_from_the_centre(_gba: &mut agb::Gba) {
+        // Hits below the middle of the paddle go down, and hits above it go up
+        for (offset, goes_down) in [(num!(10), true), (num!(-10), false)] {
+            let mut events = EventSink::default();
+            let mut paddle_a = Paddle::new(Side::Left, vec2(num!(8), num!(40)), num!(2), 3);
+            let mut paddle_b = Paddle::new(Side::Right, vec2(num!(400), num!(8)), num!(2), 3);
+
+            let centre_y = paddle_a.collision_rect().centre().y;
+            let mut ball = Ball::new(
+                vec2(num!(24), centre_y + offset - BALL_RADIUS),
+                vec2(num!(-3), num!(0)),
+            );
+            ball.state = BallState::InPlay;
+
+            ball.update(
+                &mut paddle_a,
+                &mut Opponent::Paddle(&mut paddle_b),
+                &[],
+                &mut events,
+            );
+
+            assert!(ball.velocity.x > num!(0), "the ball should bounce back");
+            assert_eq!(ball.velocity.y > num!(0), goes_down, "{offset} off centre");
+            assert!(
+                events.iter().any(|event| matches!(
+                    event,
+                    GameEvent::PaddleHit {
+                        side: Side::Left,
+                        ..
+                    }
+                )),
+                "the hit should make a sound"
+            );
+            assert!(ball.last_hit == Some(Side::Left));
+        }
+    }
+
+    #[test_case]
+    fn serves_go_forward_at_the_base_speed(_gba: &mut agb::Gba) {
+        let mut rng = Rng::new(1234);
+        let mut ball = Ball::new(vec2(num!(50), num!(50)), vec2(num!(2), num!(0.5)));
+        let base_speed = ball.velocity.magnitude();
+
+        let (mut ups, mut downs) = (0, 0);
+        for toward in [Side::Left, Side::Right].into_iter().cycle().take(100) {
+            ball.serve(&mut rng, toward);
+            let velocity = ball.velocity;
+
+            assert_eq!(velocity.x < num!(0), toward == Side::Left);
+            let slope = velocity.y.abs() / velocity.x.abs();
+            assert!(slope >= MIN_BALL_ANGLE - num!(0.01) && slope <= MAX_SERVE_ANGLE);
+            assert!((velocity.magnitude() - base_speed).abs() < num!(0.05));
+            if velocity.y < num!(0) {
+                ups += 1;
+            } else {
+                downs += 1;
+            }
+        }
+        assert!(ups > 0 && downs > 0, "serves should go both up and down");
+    }
+
+    #[test_case]
+    fn paddle_ends_deflect_more_steeply_than_the_middle(_gba: &mut agb::Gba) {
+        let paddle = Paddle::new(Side::Left, vec2(num!(8), num!(40)), num!(2), 3).collision_rect();
+        let reach = paddle.size.y / 2 + BALL_RADIUS;
+
+        let slope_at = |offset: FixedNum<8>| {
+            let mut ball = Ball::new(vec2(num!(24), num!(0)), vec2(num!(3), num!(1)));
+            ball.deflect(paddle, paddle.centre().y + reach * offset, num!(0));
+            assert!(
+                (ball.velocity.magnitude() - num!(10).sqrt()).abs() < num!(0.05),
+                "deflecting shouldn't change the speed"
+            );
+            assert!(ball.velocity.x > num!(0));
+            ball.velocity.y / ball.velocity.x
+        };
+
+        assert_eq!(slope_at(num!(0)), num!(0), "the middle should send it flat");
+        let near_middle = slope_at(num!(0.25));
+        let half_way = slope_at(num!(0.5));
+        let end = slope_at(num!(1));
+        assert!(
+            near_middle * 2 < half_way && half_way * 2 < end,
+            "the angle should grow faster towards the end"
+        );
+        assert!((end - MAX_DEFLECTION).abs() < num!(0.05));
+        assert!(
+            slope_at(num!(-1)) < num!(0),
+            "the top end should send it up"
+        );
+    }
+
+    #[test_case]
+    fn spin_curves_the_ball_then_wears_off(_gba: &mut agb::Gba) {
+        let mut ball = Ball::new(vec2(num!(24), num!(0)), vec2(num!(3), num!(0)));
+        ball.deflect(
+            Paddle::new(Side::Left, vec2(num!(8), num!(40)), num!(2), 3).collision_rect(),
+            num!(64),
+            num!(2),
+        );
+        assert!(
+            ball.spin > num!(0),
+            "a paddle moving down should curve the ball down"
+        );
+
+        let start = ball.velocity;
+        let speed = start.magnitude();
+        let mut velocity = start;
+        for _ in 0..30 {
+            velocity = rotate(velocity, ball.spin);
+            ball.spin = decay_spin(ball.spin);
+        }
+
+        // The cross product of the two headings over their lengths is the sine of the angle
+        // between them, which is close enough to the angle itself at this size
+        let turned = (start.x * velocity.y - start.y * velocity.x) / (speed * speed);
+        assert!(
+            turned > num!(0.1),
+            "the ball should have curved down by a good few degrees"
+        );
+        assert!(
+            (velocity.magnitude() - speed).abs() < num!(0.1),
+            "curving shouldn't change the speed"
+        );
+        assert_eq!(ball.spin, num!(0), "the spin should wear off");
+
+        let mut spin = -PADDLE_SPIN * 5;
+        for _ in 0..30 {
+            spin = decay_spin(spin);
+        }
+        assert_eq!(spin, num!(0), "spin the other way should wear off too");
+    }
+
+    #[test_case]
+    fn very_fast_ball_cannot_skip_past_a_paddle(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, start_pos(Side::Left), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, start_pos(Side::Right), num!(2), 3);
+
+        // Far faster than the speed limit, so a single check next frame would land well behind
+        // the paddle, off the edge of the screen, without ever touching it
+        let mut ball = Ball::new(vec2(num!(40), num!(20)), vec2(num!(-40), num!(0)));
+        ball.state = BallState::InPlay;
+
+        let conceded = ball.update(
+            &mut paddle_a,
+            &mut Opponent::Paddle(&mut paddle_b),
+            &[],
+            &mut events,
+        );
+
+        assert!(conceded.is_none(), "the ball should hit the paddle");
+        assert!(ball.velocity.x > num!(0), "the ball should bounce back");
+        assert_eq!(ball.rally, 1);
+    }
+
+    #[test_case]
+    fn ball_bounces_off_an_obstacle(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, start_pos(Side::Left), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, start_pos(Side::Right), num!(2), 3);
+        let obstacles = [Obstacle::new(vec2(num!(100), num!(72)))];
+
+        // Heading straight for the left hand side of the block
+        let mut ball = Ball::new(vec2(num!(80), num!(72)), vec2(num!(3), num!(0)));
+        ball.state = BallState::InPlay;
+
+        for _ in 0..4 {
+            ball.update(
+                &mut paddle_a,
+                &mut Opponent::Paddle(&mut paddle_b),
+                &obstacles,
+                &mut events,
+            );
+        }
+
+        assert!(ball.velocity.x < num!(0), "the ball should bounce back");
+        assert_eq!(ball.rally, 0, "obstacles don't count as hits");
+    }
+
+    #[test_case]
+    fn ball_knocks_down_a_brick(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, start_pos(Side::Left), num!(2), 3);
+        let mut bricks = alloc::vec![Brick::new(vec2(num!(100), num!(72)), 1)];
+
+        let mut ball = Ball::new(vec2(num!(80), num!(72)), vec2(num!(3), num!(0)));
+        ball.state = BallState::InPlay;
+
+        for _ in 0..4 {
+            ball.update(
+                &mut paddle_a,
+                &mut Opponent::Bricks(&mut bricks),
+                &[],
+                &mut events,
+            );
+        }
+
+        assert!(bricks.is_empty(), "the brick should break");
+        assert!(ball.velocity.x < num!(0), "the ball should bounce back");
+    }
+
+    #[test_case]
+    fn large_ball_bounces_before_leaving_the_screen(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, vec2(num!(-100), num!(8)), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, vec2(num!(400), num!(8)), num!(2), 3);
+
+        let mut ball = Ball::new(vec2(num!(100), num!(100)), vec2(num!(0.5), num!(3)))
+            .with_radius(LARGE_BALL_RADIUS);
+        ball.state = BallState::InPlay;
+
+        for _ in 0..20 {
+            ball.update(
+                &mut paddle_a,
+                &mut Opponent::Paddle(&mut paddle_b),
+                &[],
+                &mut events,
+            );
+
+            let bottom = ball.pos.y + LARGE_BALL_RADIUS * 2;
+            assert!(
+                bottom <= num!(agb::display::HEIGHT + 3),
+                "the ball shouldn't sink into the bottom of the screen"
+            );
+        }
+
+        assert!(
+            ball.velocity.y < num!(0),
+            "the ball should bounce off the bottom"
+        );
+    }
+
+    #[test_case]
+    fn wrapping_ball_comes_back_in_from_the_other_edge(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, vec2(num!(-100), num!(8)), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, vec2(num!(400), num!(8)), num!(2), 3);
+
+        let velocity = vec2(num!(0.5), num!(-3));
+        let mut ball = Ball::new(vec2(num!(100), num!(4)), velocity).with_edge_mode(EdgeMode::Wrap);
+        ball.state = BallState::InPlay;
+
+        for _ in 0..10 {
+            ball.update(
+                &mut paddle_a,
+                &mut Opponent::Paddle(&mut paddle_b),
+                &[],
+                &mut events,
+            );
+        }
+
+        assert_eq!(
+            ball.velocity, velocity,
+            "wrapping shouldn't change the velocity"
+        );
+        assert!(
+            ball.centre().y > num!(agb::display::HEIGHT / 2),
+            "the ball should have come back in at the bottom"
+        );
+        assert_eq!(events.iter().count(), 0, "there's no wall to hit");
+    }
+
+    #[test_case]
+    fn balls_meeting_head_on_swap_directions(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut balls = [
+            Ball::new(vec2(num!(100), num!(80)), vec2(num!(2), num!(0))),
+            Ball::new(vec2(num!(107), num!(80)), vec2(num!(-1), num!(0))),
+        ];
+        for ball in &mut balls {
+            ball.launch();
+        }
+
+        collide_balls(&mut balls, &mut events);
+
+        assert_eq!(balls[0].velocity, vec2(num!(-1), num!(0)));
+        assert_eq!(balls[1].velocity, vec2(num!(2), num!(0)));
+        assert_eq!(
+            balls[1].pos.x - balls[0].pos.x,
+            BALL_RADIUS * 2,
+            "the balls should have been pushed apart until they just touch"
+        );
+        assert_eq!(events.iter().count(), 1);
+    }
+
+    #[test_case]
+    fn balls_on_top_of_each_other_push_apart_gently(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let velocity = vec2(num!(2), num!(0.5));
+        let mut balls = [
+            Ball::new(vec2(num!(100), num!(80)), velocity),
+            Ball::new(vec2(num!(100), num!(80)), velocity),
+        ];
+        for ball in &mut balls {
+            ball.launch();
+        }
+
+        collide_balls(&mut balls, &mut events);
+
+        for ball in &balls {
+            assert_eq!(
+                ball.velocity, velocity,
+                "neither ball should be sent flying"
+            );
+        }
+        assert_eq!(balls[1].pos.y - balls[0].pos.y, BALL_RADIUS * 2);
+
+        // Now they're travelling along side by side, just touching
+        collide_balls(&mut balls, &mut events);
+        assert_eq!(
+            events.iter().count(),
+            0,
+            "balls that aren't coming together shouldn't bounce"
+        );
+    }
+
+    #[test_case]
+    fn ball_bounces_off_the_edge_of_an_inset_court(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        let mut paddle_a = Paddle::new(Side::Left, vec2(num!(-100), num!(8)), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, vec2(num!(400), num!(8)), num!(2), 3);
+
+        // The top wall is pushed down to make room for a scoreboard
+        let court = Rect::new(vec2(num!(0), num!(24)), vec2(num!(240), num!(136)));
+        let mut ball =
+            Ball::new(vec2(num!(100), num!(30)), vec2(num!(1), num!(-2))).with_court(court);
+        ball.launch();
+
+        for _ in 0..10 {
+            ball.update(
+                &mut paddle_a,
+                &mut Opponent::Paddle(&mut paddle_b),
+                &[],
+                &mut events,
+            );
+            assert!(
+                ball.pos.y >= num!(20),
+                "the ball shouldn't get far past the wall"
+            );
+        }
+
+        assert!(
+            ball.velocity.y > num!(0),
+            "the ball should have come off the wall"
+        );
+        assert!(
+            events
+                .iter()
+                .any(|event| matches!(event, GameEvent::WallHit { pos, .. } if pos.y == num!(24))),
+            "the wall should be where the court starts"
+        );
+    }
+
+    #[test_case]
+    fn player_aims_the_serve_within_a_forward_cone(_gba: &mut agb::Gba) {
+        let mut ball = Ball::new(vec2(num!(50), num!(50)), vec2(num!(2), num!(0.5)));
+        ball.serve(&mut Rng::new(1), Side::Right);
+        let speed = ball.speed();
+
+        let up = Input::new(Button::UP, Button::UP);
+        for _ in 0..100 {
+            ball.update_serve(&up, false);
+        }
+        assert!(!ball.is_in_play(), "aiming shouldn't serve the ball");
+        assert!(ball.velocity.x > num!(0), "the serve should still head out");
+        let slope = ball.velocity.y / ball.velocity.x;
+        assert!(
+            (slope + MAX_SERVE_ANGLE).abs() < num!(0.02),
+            "held up, it aims as high as it goes"
+        );
+        assert!((ball.speed() - speed).abs() < num!(0.05));
+
+        ball.update_serve(
+            &Input::new(Button::DOWN | Button::A, Button::empty()),
+            false,
+        );
+        assert!(ball.is_in_play());
+        assert!(
+            ball.velocity.y < num!(0),
+            "one frame of aiming shouldn't flip the serve"
+        );
+    }
+
+    #[test_case]
+    fn watchdog_only_nudges_a_stuck_rally_once(_gba: &mut agb::Gba) {
+        let mut rng = Rng::new(7);
+        let velocity = vec2(num!(2), num!(0));
+        let mut ball = Ball::new(vec2(num!(100), num!(50)), velocity);
+        ball.launch();
+
+        ball.rally = WATCHDOG_RALLY - 1;
+        assert!(!ball.watchdog(&mut rng), "a long rally isn't stuck yet");
+        assert_eq!(ball.velocity, velocity);
+
+        ball.rally = WATCHDOG_RALLY;
+        assert!(ball.watchdog(&mut rng));
+        assert!(
+            ball.velocity.y != num!(0),
+            "the bounce should be knocked off its line"
+        );
+        assert!((ball.speed() - num!(2)).abs() < num!(0.05));
+        assert!(
+            !ball.watchdog(&mut rng),
+            "once is enough until the rally goes on"
+        );
+    }
+
+    #[test_case]
+    fn watchdog_keeps_a_steep_ball_playable(_gba: &mut agb::Gba) {
+        for seed in 0..8 {
+            let mut rng = Rng::new(seed);
+            let mut ball = Ball::new(vec2(num!(100), num!(50)), vec2(num!(2), num!(3)));
+            ball.launch();
+            ball.rally = WATCHDOG_RALLY;
+
+            assert!(ball.watchdog(&mut rng));
+            let velocity = ball.velocity;
+            assert!(
+                velocity.y.abs() <= velocity.x.abs() * MAX_BALL_ANGLE + num!(0.05),
+                "the nudge shouldn't leave the ball steeper than the steepest shot"
+            );
+        }
+    }
+
+    #[test_case]
+    fn faster_serves_have_a_higher_top_speed(_gba: &mut agb::Gba) {
+        let base = vec2(num!(2), num!(0.5));
+        let top_speed = |scale: FixedNum<8>| {
+            let mut ball = Ball::new(vec2(num!(100), num!(80)), base * scale);
+            ball.velocity = vec2(num!(20), num!(5));
+            ball.clamp_speed();
+            ball.speed()
+        };
+
+        let (normal, fast) = (top_speed(num!(1)), top_speed(num!(1.5)));
+        assert!(
+            normal < fast,
+            "the faster ball should be allowed to go faster"
+        );
+        assert!(
+            (normal * num!(1.5) - fast).abs() < num!(0.1),
+            "the top speed should scale with the serve"
+        );
+    }
+
+    #[test_case]
+    fn gravity_cannot_speed_the_ball_up_forever(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        // Out of the way, so the ball only ever bounces between the top and bottom walls
+        let mut paddle_a = Paddle::new(Side::Left, vec2(num!(-100), num!(8)), num!(2), 3);
+        let mut paddle_b = Paddle::new(Side::Right, vec2(num!(400), num!(8)), num!(2), 3);
+
+        let mut ball = Ball::new(vec2(num!(100), num!(20)), vec2(num!(0.5), num!(0)))
+            .with_gravity(vec2(num!(0), num!(1)));
+        ball.state = BallState::InPlay;
+
+        for _ in 0..200 {
+            ball.update(
+                &mut paddle_a,
+                &mut Opponent::Paddle(&mut paddle_b),
+                &[],
+                &mut events,
+            );
+            ball.pos.x = num!(100);
+
+            assert!(
+                ball.velocity.y.abs() <= ball.max_speed(),
+                "the ball should stay under the speed limit"
+            );
+        }
+    }
+}
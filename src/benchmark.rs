@@ -0,0 +1,150 @@
+//! A hidden mode for tuning the physics and the CPU. It plays a batch of matches with the CPU on
+//! both sides as fast as the console can go, without drawing anything, then writes up how they
+//! went to the emulator's log.
+
+use crate::event::EventSink;
+use crate::input::Input;
+use crate::paddle::Difficulty;
+use crate::rng::Rng;
+use crate::timestep::{seconds_for_steps, steps_for_seconds};
+use crate::{FrameSounds, GameConfig, GamePlay, MatchResult, MatchState};
+
+/// How many matches each run of the benchmark plays
+pub const BENCHMARK_MATCHES: u32 = 20;
+/// A match still going after this long is given up on, as the CPU can end up in a rally that
+/// never ends
+const MAX_MATCH_SECONDS: u32 = 30 * 60;
+
+/// How a batch of CPU against CPU matches went, all added up
+#[derive(Default)]
+pub struct BenchmarkResults {
+    matches: u32,
+    /// Matches given up on for going on too long, which don't count towards anything else
+    unfinished: u32,
+    /// Matches won by the left paddle then the right, with a draw counting for neither
+    wins: [u32; 2],
+    points: u32,
+    hits: u32,
+    steps: u32,
+    longest_rally: u32,
+}
+
+impl BenchmarkResults {
+    /// The average number of hits per point
+    fn average_rally(&self) -> u32 {
+        self.hits.checked_div(self.points).unwrap_or(0)
+    }
+
+    /// The average match length, in seconds at full speed
+    fn average_match_seconds(&self) -> u32 {
+        let finished = self.matches - self.unfinished;
+        seconds_for_steps(self.steps.checked_div(finished).unwrap_or(0))
+    }
+
+    /// Writes the results to the emulator's log, along with the `seed` they were played from so
+    /// the same run can be played again
+    pub fn log(&self, seed: u32) {
+        agb::println!(
+            "Benchmark from seed {}: {} matches, {} unfinished",
+            seed,
+            self.matches,
+            self.unfinished
+        );
+        agb::println!("Wins: {} left, {} right", self.wins[0], self.wins[1]);
+        agb::println!(
+            "Points: {}, hits: {}, average rally: {}, longest rally: {}",
+            self.points,
+            self.hits,
+            self.average_rally(),
+            self.longest_rally
+        );
+        agb::println!(
+            "Average match: {}s over {} steps",
+            self.average_match_seconds(),
+            self.steps
+        );
+    }
+}
+
+/// Plays `matches` matches between two CPUs at `difficulty`, each seeded one on from the last
+/// starting at `seed`
+pub fn run(
+    difficulty: Difficulty,
+    config: GameConfig,
+    seed: u32,
+    matches: u32,
+) -> BenchmarkResults {
+    let mut results = BenchmarkResults::default();
+    let mut events = EventSink::default();
+    // Nothing gets played, these are just somewhere for the sounds to go
+    let mut sounds = FrameSounds::default();
+    let max_steps = steps_for_seconds(MAX_MATCH_SECONDS);
+
+    for i in 0..matches {
+        let mut game = GamePlay::new(false, difficulty, config, Rng::new(seed.wrapping_add(i)));
+        game.demo = true;
+        let mut match_state = MatchState::new(game, config.games_to_win);
+
+        let result = loop {
+            match_state
+                .game
+                .step(&Input::default(), &mut events, &mut sounds);
+            // Nothing's drawn, so every step stands for a frame at full speed
+            match_state.game.count_frames(1);
+
+            let game = &match_state.game;
+            if game.stats.frames >= max_steps {
+                break None;
+            }
+            if let Some(result) = game.result()
+                && let Some(result) = match_state.finish_game(result)
+            {
+                break Some(result);
+            }
+        };
+
+        results.matches += 1;
+        let Some(result) = result else {
+            results.unfinished += 1;
+            continue;
+        };
+        match result {
+            MatchResult::P1Win => results.wins[0] += 1,
+            MatchResult::P2Win => results.wins[1] += 1,
+            MatchResult::Draw => {}
+        }
+
+        let stats = match_state.game.stats();
+        results.points += stats.points;
+        results.hits += stats.hits;
+        results.steps += stats.frames;
+        results.longest_rally = results.longest_rally.max(stats.longest_rally);
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WinCondition;
+
+    #[test_case]
+    fn every_match_gets_counted(_gba: &mut agb::Gba) {
+        let config = GameConfig {
+            win_condition: WinCondition::Lives,
+            starting_health: 1,
+            games_to_win: 1,
+            ..GameConfig::default()
+        };
+        let results = run(Difficulty::Easy, config, 1, 3);
+
+        assert_eq!(results.matches, 3);
+        assert_eq!(
+            results.wins[0] + results.wins[1] + results.unfinished,
+            3,
+            "one life each can't end in a draw"
+        );
+        assert!(results.points >= results.matches - results.unfinished);
+    }
+}
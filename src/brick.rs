@@ -0,0 +1,74 @@
+//! The wall of bricks that takes the place of the right hand paddle in brick breaker games.
+
+use agb::display::Priority;
+use agb::display::object::Object;
+use agb::fixnum::{FixedNum, Rect, Vector2D, num, vec2};
+use alloc::vec::Vec;
+
+use crate::Side;
+use crate::paddle::start_pos_in;
+use crate::sprite_budget::ObjectSink;
+use crate::sprites;
+
+/// How the wall is laid out. The columns further back take more hits to break, and the back one
+/// stands where the right paddle would.
+const BRICK_COLUMNS: i32 = 3;
+const BRICK_ROWS: i32 = 8;
+/// How far down from the top of the court the wall starts
+const WALL_TOP: i32 = 6;
+/// The distance from the start of one brick to the start of the next, leaving a gap between them
+const COLUMN_SPACING: i32 = 12;
+const ROW_SPACING: i32 = 19;
+
+pub struct Brick {
+    pos: Vector2D<FixedNum<8>>,
+    /// How many more hits it takes to break the brick
+    health: u8,
+}
+
+impl Brick {
+    pub fn new(pos: Vector2D<FixedNum<8>>, health: u8) -> Self {
+        Self { pos, health }
+    }
+
+    /// A full wall of bricks at the right hand end of `court`, ready for a new game, with the back
+    /// of it `paddle_inset` in from the end like the paddle it replaces
+    pub fn wall(court: Rect<FixedNum<8>>, paddle_inset: i32) -> Vec<Self> {
+        let front = start_pos_in(Side::Right, court, paddle_inset).x.floor()
+            - (BRICK_COLUMNS - 1) * COLUMN_SPACING;
+        let top = court.top_left().y.floor() + WALL_TOP;
+        (0..BRICK_COLUMNS)
+            .flat_map(|column| {
+                (0..BRICK_ROWS).map(move |row| {
+                    let x = front + column * COLUMN_SPACING;
+                    let y = top + row * ROW_SPACING;
+                    Self::new(vec2(FixedNum::new(x), FixedNum::new(y)), column as u8 + 1)
+                })
+            })
+            .collect()
+    }
+
+    pub fn collision_rect(&self) -> Rect<FixedNum<8>> {
+        Rect::new(self.pos, vec2(num!(8), num!(16)))
+    }
+
+    /// Takes a hit off the brick, returning whether that broke it
+    pub fn hit(&mut self) -> bool {
+        self.health = self.health.saturating_sub(1);
+        self.is_broken()
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.health == 0
+    }
+
+    pub fn show(&self, frame: &mut impl ObjectSink) {
+        // The colour shows how many hits are left
+        let sprite = sprites::BRICK.sprite(usize::from(self.health.max(1) - 1));
+        frame.show(
+            Object::new(sprite)
+                .set_pos(self.pos.round())
+                .set_priority(Priority::P1),
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! The shapes things collide as, and the tests for whether they overlap.
+
+use agb::fixnum::{FixedNum, Number, Rect, Vector2D, num, vec2};
+
+pub struct Circle<T: Number> {
+    pub pos: Vector2D<T>,
+    pub radius: T,
+}
+
+pub trait Touches<T> {
+    fn touches(&self, rhs: T) -> bool;
+}
+
+impl<T: Number> Circle<T> {
+    pub fn new(pos: Vector2D<T>, radius: T) -> Self {
+        Self { pos, radius }
+    }
+    pub fn centre(&self) -> Vector2D<T> {
+        self.pos + vec2(self.radius, self.radius)
+    }
+}
+
+impl<const N: usize> Circle<FixedNum<N>> {
+    /// The direction pointing out of `rect` towards the circle's centre, from the closest point of
+    /// `rect` to it, or `None` if they don't touch. A hit on a corner gives a diagonal normal.
+    pub fn collision_normal(&self, rect: Rect<FixedNum<N>>) -> Option<Vector2D<FixedNum<N>>> {
+        let centre = self.centre();
+        let offset = centre - rect.clamp_point(centre);
+        let distance_squared = offset.magnitude_squared();
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+
+        // Too small to normalise, which mostly means the centre is inside the rect. Push out
+        // through whichever side it's closest to instead.
+        if distance_squared == num!(0) {
+            let (top_left, bottom_right) = (rect.top_left(), rect.bottom_right());
+            let sides = [
+                (centre.x - top_left.x, vec2(num!(-1), num!(0))),
+                (bottom_right.x - centre.x, vec2(num!(1), num!(0))),
+                (centre.y - top_left.y, vec2(num!(0), num!(-1))),
+                (bottom_right.y - centre.y, vec2(num!(0), num!(1))),
+            ];
+
+            return sides
+                .into_iter()
+                .min_by_key(|(distance, _)| *distance)
+                .map(|(_, normal)| normal);
+        }
+
+        Some(offset.normalise())
+    }
+}
+
+impl<const N: usize> Touches<Rect<FixedNum<N>>> for Circle<FixedNum<N>> {
+    fn touches(&self, rect: Rect<FixedNum<N>>) -> bool {
+        // which edge is closest;
+        let test_x = match self.centre().x {
+            cx if cx < rect.top_left().x => rect.top_left().x,
+            cx if cx > rect.bottom_right().x => rect.bottom_right().x,
+            cx => cx,
+        };
+        let test_y = match self.centre().y {
+            cy if cy < rect.top_left().y => rect.top_left().y,
+            cy if cy > rect.bottom_left().y => rect.bottom_left().y,
+            cy => cy,
+        };
+
+        let dist_x = self.centre().x - test_x;
+        let dist_y = self.centre().y - test_y;
+
+        // This runs for every ball against every paddle each frame, and a fixed point sqrt is
+        // slow on the GBA. Both sides are positive, so comparing their squares gives the same
+        // answer without needing one.
+        (dist_x * dist_x) + (dist_y * dist_y) <= self.radius * self.radius
+    }
+}
+
+impl<const N: usize> Touches<Circle<FixedNum<N>>> for Circle<FixedNum<N>> {
+    fn touches(&self, other: Circle<FixedNum<N>>) -> bool {
+        // Comparing the squared distance means we don't need a sqrt
+        let distance_squared = (self.centre() - other.centre()).magnitude_squared();
+        let touching_distance = self.radius + other.radius;
+
+        distance_squared <= touching_distance * touching_distance
+    }
+}
+
+/// Unlike agb's own `Rect::touches`, rectangles that only share an edge count as touching, to match
+/// the circle checks. As `Rect` has its own inherent `touches`, call this one as
+/// `Touches::touches(&a, b)`.
+impl<T: Number> Touches<Rect<T>> for Rect<T> {
+    fn touches(&self, other: Rect<T>) -> bool {
+        let (a_min, a_max) = (self.top_left(), self.bottom_right());
+        let (b_min, b_max) = (other.top_left(), other.bottom_right());
+
+        // They're apart if there's a gap between them along either axis
+        a_min.x <= b_max.x && b_min.x <= a_max.x && a_min.y <= b_max.y && b_min.y <= a_max.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect() -> Rect<FixedNum<8>> {
+        Rect::new(vec2(num!(20), num!(20)), vec2(num!(10), num!(40)))
+    }
+
+    #[test_case]
+    fn circle_overlapping_an_edge_touches(_gba: &mut agb::Gba) {
+        // Centred 6 pixels to the right of the rect's right edge, which is within the radius
+        let circle = Circle::new(vec2(num!(28), num!(30)), num!(8));
+        assert!(circle.touches(rect()));
+    }
+
+    #[test_case]
+    fn circle_inside_touches(_gba: &mut agb::Gba) {
+        let circle = Circle::new(vec2(num!(17), num!(32)), num!(8));
+        assert!(circle.touches(rect()));
+    }
+
+    #[test_case]
+    fn circle_clear_of_the_rect_does_not_touch(_gba: &mut agb::Gba) {
+        let circle = Circle::new(vec2(num!(31), num!(30)), num!(8));
+        assert!(!circle.touches(rect()));
+    }
+
+    #[test_case]
+    fn circle_near_a_corner_uses_the_distance_to_it(_gba: &mut agb::Gba) {
+        // Within the radius of the top left corner along each axis, but not diagonally
+        let circle = Circle::new(vec2(num!(6), num!(6)), num!(8));
+        assert!(!circle.touches(rect()));
+
+        let circle = Circle::new(vec2(num!(8), num!(8)), num!(8));
+        assert!(circle.touches(rect()));
+    }
+
+    #[test_case]
+    fn small_circle_well_inside_touches(_gba: &mut agb::Gba) {
+        // Centred in the middle of the rect, and not reaching any of its edges
+        let circle = Circle::new(vec2(num!(23), num!(38)), num!(2));
+        assert!(circle.touches(rect()));
+    }
+
+    #[test_case]
+    fn circle_grazing_each_edge_touches(_gba: &mut agb::Gba) {
+        // Each centre is exactly the radius away from one of the edges
+        for pos in [(30, 32), (4, 32), (17, 4), (17, 60)] {
+            let circle = Circle::new(vec2(FixedNum::new(pos.0), FixedNum::new(pos.1)), num!(8));
+            assert!(circle.touches(rect()), "{pos:?} should touch");
+        }
+    }
+
+    #[test_case]
+    fn circle_past_each_edge_does_not_touch(_gba: &mut agb::Gba) {
+        // The same as the grazing circles, moved a pixel further away
+        for pos in [(31, 32), (3, 32), (17, 3), (17, 61)] {
+            let circle = Circle::new(vec2(FixedNum::new(pos.0), FixedNum::new(pos.1)), num!(8));
+            assert!(!circle.touches(rect()), "{pos:?} shouldn't touch");
+        }
+    }
+
+    #[test_case]
+    fn circle_touching_a_corner_at_its_radius_touches(_gba: &mut agb::Gba) {
+        // 6 across and 8 up or down from a corner makes the distance to it exactly 10
+        let circle = Circle::new(vec2(num!(4), num!(2)), num!(10));
+        assert!(circle.touches(rect()), "top left");
+
+        let circle = Circle::new(vec2(num!(26), num!(58)), num!(10));
+        assert!(circle.touches(rect()), "bottom right");
+
+        let circle = Circle::new(vec2(num!(27), num!(59)), num!(10));
+        assert!(!circle.touches(rect()), "just past the bottom right");
+    }
+
+    #[test_case]
+    fn works_at_other_precisions(_gba: &mut agb::Gba) {
+        let rect: Rect<FixedNum<4>> = Rect::new(vec2(num!(20), num!(20)), vec2(num!(10), num!(40)));
+        assert!(Circle::new(vec2(num!(28), num!(30)), num!(8)).touches(rect));
+        assert!(!Circle::new(vec2(num!(31), num!(30)), num!(8)).touches(rect));
+    }
+
+    #[test_case]
+    fn normal_points_out_of_the_nearest_edge(_gba: &mut agb::Gba) {
+        let circle = Circle::new(vec2(num!(28), num!(30)), num!(8));
+        assert_eq!(
+            circle.collision_normal(rect()),
+            Some(vec2(num!(1), num!(0)))
+        );
+
+        let circle = Circle::new(vec2(num!(31), num!(30)), num!(8));
+        assert_eq!(circle.collision_normal(rect()), None);
+    }
+
+    #[test_case]
+    fn normal_from_a_corner_is_diagonal(_gba: &mut agb::Gba) {
+        // Centred 4 pixels up and left of the top left corner
+        let circle = Circle::new(vec2(num!(8), num!(8)), num!(8));
+        let normal = circle.collision_normal(rect()).unwrap();
+        assert!(normal.x < num!(0) && normal.x == normal.y, "{normal:?}");
+    }
+
+    #[test_case]
+    fn normal_from_inside_uses_the_closest_side(_gba: &mut agb::Gba) {
+        // Centred 2 pixels in from the rect's left edge
+        let circle = Circle::new(vec2(num!(14), num!(32)), num!(8));
+        assert_eq!(
+            circle.collision_normal(rect()),
+            Some(vec2(num!(-1), num!(0)))
+        );
+    }
+
+    #[test_case]
+    fn rects_sharing_an_edge_touch(_gba: &mut agb::Gba) {
+        let beside = Rect::new(vec2(num!(30), num!(30)), vec2(num!(5), num!(5)));
+        assert!(Touches::touches(&rect(), beside));
+
+        let apart = Rect::new(vec2(num!(31), num!(30)), vec2(num!(5), num!(5)));
+        assert!(!Touches::touches(&rect(), apart));
+    }
+
+    #[test_case]
+    fn rect_inside_another_touches(_gba: &mut agb::Gba) {
+        let inside = Rect::new(vec2(num!(22), num!(30)), vec2(num!(4), num!(4)));
+        assert!(Touches::touches(&rect(), inside));
+        assert!(Touches::touches(&inside, rect()));
+    }
+
+    #[test_case]
+    fn circles_just_touching_touch(_gba: &mut agb::Gba) {
+        // The centres are exactly the sum of the radii apart
+        let a: Circle<FixedNum<8>> = Circle::new(vec2(num!(0), num!(0)), num!(8));
+        let b = Circle::new(vec2(num!(12), num!(16)), num!(2));
+        assert!(a.touches(b));
+    }
+
+    #[test_case]
+    fn overlapping_circles_touch(_gba: &mut agb::Gba) {
+        let a: Circle<FixedNum<8>> = Circle::new(vec2(num!(0), num!(0)), num!(8));
+        let b = Circle::new(vec2(num!(4), num!(4)), num!(8));
+        assert!(a.touches(b));
+    }
+
+    #[test_case]
+    fn separate_circles_do_not_touch(_gba: &mut agb::Gba) {
+        let a: Circle<FixedNum<8>> = Circle::new(vec2(num!(0), num!(0)), num!(8));
+        let b = Circle::new(vec2(num!(30), num!(0)), num!(8));
+        assert!(!a.touches(b));
+    }
+}
@@ -0,0 +1,327 @@
+//! The things that can drive a paddle. Each frame a controller gets a look at the game and says
+//! which way it wants its paddle to go, and the paddle does the rest, so a person, the CPU or the
+//! other end of a link cable can all take either side.
+
+use agb::fixnum::{FixedNum, Rect, Vector2D, num};
+use agb::input::Button;
+
+use crate::ball::Ball;
+use crate::input::Input;
+use crate::paddle::Difficulty;
+use crate::{Side, fixed};
+
+/// How close the CPU has to get to where it's aiming before it stops moving
+const AI_DEAD_ZONE: FixedNum<8> = fixed(4.);
+
+/// How much slower to react and further off to aim for each point the CPU is ahead when it's
+/// rubber banding, or quicker and closer for each point behind
+const RUBBER_BAND_DELAY: i32 = 2;
+const RUBBER_BAND_ERROR: FixedNum<8> = fixed(3.);
+
+/// However far ahead or behind it gets, a rubber banding CPU always takes another look at the ball
+/// at least this often and always aims at least this close, so it never gives up completely. It
+/// also always waits a frame and misses by a little, so it's never perfect either.
+const RUBBER_BAND_DELAYS: (u16, u16) = (2, 24);
+const RUBBER_BAND_ERRORS: (FixedNum<8>, FixedNum<8>) = (fixed(1.), fixed(30.));
+
+/// How long ahead the CPU looks to see where the other paddle's going, in frames
+const OPPONENT_LOOKAHEAD: i32 = 8;
+
+/// Where a paddle is and how it's moving
+#[derive(Clone, Copy)]
+pub struct PaddleState {
+    /// The part of the paddle that hits the ball
+    pub rect: Rect<FixedNum<8>>,
+    /// How fast it's going along its track, down the screen or to the right when positive
+    pub velocity: FixedNum<8>,
+}
+
+/// What a controller can see of the game when it decides where its paddle goes
+pub struct GameView<'a> {
+    side: Side,
+    paddle: Rect<FixedNum<8>>,
+    balls: &'a [Ball],
+    opponent: Option<PaddleState>,
+}
+
+impl<'a> GameView<'a> {
+    pub fn new(side: Side, paddle: Rect<FixedNum<8>>, balls: &'a [Ball]) -> Self {
+        Self {
+            side,
+            paddle,
+            balls,
+            opponent: None,
+        }
+    }
+
+    /// Lets the controller see the paddle across the court too
+    pub fn with_opponent(mut self, opponent: Option<PaddleState>) -> Self {
+        self.opponent = opponent;
+        self
+    }
+
+    /// Which end of the court the paddle being moved is at
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// The part of the paddle being moved that hits the ball
+    pub fn paddle(&self) -> Rect<FixedNum<8>> {
+        self.paddle
+    }
+
+    pub fn balls(&self) -> &'a [Ball] {
+        self.balls
+    }
+
+    /// The paddle across the court, if there is one. There isn't when the bricks stand in for it,
+    /// or once it's been knocked out of a four player game.
+    pub fn opponent(&self) -> Option<PaddleState> {
+        self.opponent
+    }
+}
+
+pub trait PaddleController {
+    /// Which way the paddle should go this frame: negative for up, positive for down, or zero to
+    /// let it slow to a stop. The paddles along the top and bottom go left and right instead.
+    /// Only the direction counts, as the paddle speeds up and slows down at its own rate.
+    fn desired_move(&mut self, view: &GameView) -> FixedNum<8>;
+
+    /// True if it's the CPU moving the paddle rather than a person
+    fn is_cpu(&self) -> bool {
+        false
+    }
+}
+
+/// Someone holding `up` and `down` on a controller
+pub struct HumanController {
+    input: Input,
+    up: Button,
+    down: Button,
+}
+
+impl HumanController {
+    pub fn new(input: Input, up: Button, down: Button) -> Self {
+        Self { input, up, down }
+    }
+
+    /// Moving with the D-pad, the usual way
+    pub fn dpad(input: &Input) -> Self {
+        Self::new(*input, Button::UP, Button::DOWN)
+    }
+
+    /// Moving with A and B, for shared controller practice where one person plays both sides
+    /// from the same pad
+    pub fn face_buttons(input: &Input) -> Self {
+        Self::new(*input, Button::A, Button::B)
+    }
+}
+
+impl PaddleController for HumanController {
+    fn desired_move(&mut self, _view: &GameView) -> FixedNum<8> {
+        match (
+            self.input.is_pressed(self.up),
+            self.input.is_pressed(self.down),
+        ) {
+            (true, false) => num!(-1),
+            (false, true) => num!(1),
+            (false, false) | (true, true) => num!(0),
+        }
+    }
+}
+
+/// The CPU, which follows the ball with a delay and some error depending on the difficulty
+pub struct AiController {
+    difficulty: Difficulty,
+    /// How many points the CPU is ahead by, or behind if negative, when it's rubber banding
+    lead: Option<i32>,
+    target: FixedNum<8>,
+    retarget_timer: u16,
+    retarget_count: u16,
+}
+
+impl AiController {
+    pub fn new(difficulty: Difficulty) -> Self {
+        Self {
+            difficulty,
+            lead: None,
+            target: num!(agb::display::HEIGHT / 2),
+            retarget_timer: 0,
+            retarget_count: 0,
+        }
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Rubber bands the CPU to keep the game close, playing worse the further it's ahead and
+    /// better the further it's behind. None plays at the difficulty and nothing else.
+    pub fn set_lead(&mut self, lead: Option<i32>) {
+        self.lead = lead;
+    }
+
+    /// How many frames to wait before taking another look at the ball
+    fn reaction_delay(&self) -> u16 {
+        let delay = self.difficulty.reaction_delay();
+        let Some(lead) = self.lead else {
+            return delay;
+        };
+
+        let (min, max) = RUBBER_BAND_DELAYS;
+        (i32::from(delay) + lead * RUBBER_BAND_DELAY).clamp(min.into(), max.into()) as u16
+    }
+
+    /// How far off the aim can be
+    fn aim_error(&self) -> FixedNum<8> {
+        let error = self.difficulty.aim_error();
+        let Some(lead) = self.lead else {
+            return error;
+        };
+
+        let (min, max) = RUBBER_BAND_ERRORS;
+        (error + RUBBER_BAND_ERROR * lead).clamp(min, max)
+    }
+
+    /// Where along the paddle's track to aim for, given that the ball reaches the paddle at
+    /// `front` across the court
+    fn target(&mut self, ball: &Ball, side: Side, front: FixedNum<8>) -> FixedNum<8> {
+        if self.retarget_timer == 0 {
+            self.retarget_timer = self.reaction_delay();
+            self.retarget_count = self.retarget_count.wrapping_add(1);
+
+            // Cycle the aim above, onto and below the ball so the mistakes aren't all one way
+            let error = self.aim_error();
+            let error = match self.retarget_count % 3 {
+                0 => -error,
+                1 => num!(0),
+                _ => error,
+            };
+
+            // Aim for where the centre of the ball will be when it reaches us
+            let arrival = if side.is_horizontal() {
+                ball.predict_x_at_y(front)
+            } else {
+                ball.predict_y_at_x(front)
+            };
+            self.target = arrival + ball.radius() + error;
+        }
+        self.retarget_timer -= 1;
+
+        self.target
+    }
+}
+
+impl PaddleController for AiController {
+    fn is_cpu(&self) -> bool {
+        true
+    }
+
+    fn desired_move(&mut self, view: &GameView) -> FixedNum<8> {
+        // Go after whichever ball will reach us first. If they're all heading away then the next
+        // one back will come off the other paddle, so wait in line with where that's going,
+        // or with no other paddle just keep an eye on any of the balls.
+        let balls = view.balls();
+        let paddle = view.paddle();
+        let side = view.side();
+        let outward = side.outward();
+        let along = |point: Vector2D<FixedNum<8>>| {
+            if side.is_horizontal() {
+                point.x
+            } else {
+                point.y
+            }
+        };
+        let incoming = balls
+            .iter()
+            .filter(|ball| ball.velocity().dot(outward) > num!(0))
+            .max_by_key(|ball| ball.pos().dot(outward));
+
+        let target = match (incoming, view.opponent()) {
+            (None, Some(opponent)) => {
+                along(opponent.rect.centre()) + opponent.velocity * OPPONENT_LOOKAHEAD
+            }
+            (incoming, _) => {
+                let ball = incoming.unwrap_or(&balls[0]);
+                // The ball reaches us once its near edge touches the front of the paddle
+                let front = match side {
+                    Side::Left => paddle.bottom_right().x,
+                    Side::Right => paddle.top_left().x - ball.radius() * 2,
+                    Side::Top => paddle.bottom_right().y,
+                    Side::Bottom => paddle.top_left().y - ball.radius() * 2,
+                };
+                self.target(ball, side, front)
+            }
+        };
+        let middle = along(paddle.centre());
+
+        // Don't bother moving if we're close enough, otherwise the paddle jitters back and forth
+        // around the ball's position
+        if target < middle - AI_DEAD_ZONE {
+            num!(-1)
+        } else if target > middle + AI_DEAD_ZONE {
+            num!(1)
+        } else {
+            num!(0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paddle::{Paddle, start_pos};
+    use agb::fixnum::vec2;
+
+    #[test_case]
+    fn cpu_goes_after_the_ball_from_either_side(_gba: &mut agb::Gba) {
+        let left = Paddle::new(Side::Left, start_pos(Side::Left), num!(2), 3);
+        let right = Paddle::new(Side::Right, start_pos(Side::Right), num!(2), 3);
+
+        // Low down in the middle, and the paddles are both at the top
+        for (side, paddle, x_speed) in [
+            (Side::Left, left.collision_rect(), num!(-1)),
+            (Side::Right, right.collision_rect(), num!(1)),
+        ] {
+            let balls = [Ball::new(
+                vec2(num!(116), num!(130)),
+                vec2(x_speed, num!(0)),
+            )];
+            let view = GameView::new(side, paddle, &balls);
+            let mut ai = AiController::new(Difficulty::Hard);
+            assert!(ai.desired_move(&view) > num!(0), "the CPU should move down");
+        }
+    }
+
+    #[test_case]
+    fn rubber_banding_stays_in_bounds(_gba: &mut agb::Gba) {
+        let mut ai = AiController::new(Difficulty::Hard);
+        assert_eq!(ai.reaction_delay(), 1);
+        assert_eq!(ai.aim_error(), num!(0));
+
+        // Even level, it's not quite as sharp as Hard usually is
+        ai.set_lead(Some(0));
+        assert!(ai.reaction_delay() > 1);
+        assert!(ai.aim_error() > num!(0));
+
+        // Miles behind, it gets better but never perfect
+        ai.set_lead(Some(-20));
+        assert_eq!(ai.reaction_delay(), RUBBER_BAND_DELAYS.0);
+        assert_eq!(ai.aim_error(), RUBBER_BAND_ERRORS.0);
+
+        // Miles ahead, it gets worse but still keeps an eye on the ball
+        let mut ai = AiController::new(Difficulty::Easy);
+        ai.set_lead(Some(20));
+        assert_eq!(ai.reaction_delay(), RUBBER_BAND_DELAYS.1);
+        assert_eq!(ai.aim_error(), RUBBER_BAND_ERRORS.1);
+
+        let mut aim_error_with_lead = |lead| {
+            ai.set_lead(Some(lead));
+            ai.aim_error()
+        };
+        assert!(
+            aim_error_with_lead(-1) < aim_error_with_lead(1),
+            "being behind should sharpen the aim"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Things that happen during a step of the game that something other than the game itself might
+//! want to react to. The physics only records them, and it's up to whoever drains the sink to
+//! play sounds, shake the screen or spray particles, so the physics can run without any of that.
+
+use agb::fixnum::{FixedNum, Vector2D};
+
+use crate::Side;
+
+/// The most events kept between drains. Any more are dropped, which only loses some effects.
+const MAX_EVENTS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GameEvent {
+    /// The ball bounced off the paddle on `side`, at `pos` on its front face. `speed` is how close
+    /// the ball is to its top speed, from 0 to 1.
+    PaddleHit {
+        side: Side,
+        pos: Vector2D<FixedNum<8>>,
+        speed: FixedNum<8>,
+    },
+    /// The ball bounced off the top or bottom of the screen at `pos`, with `normal` pointing back
+    /// into the field
+    WallHit {
+        pos: Vector2D<FixedNum<8>>,
+        normal: Vector2D<FixedNum<8>>,
+        speed: FixedNum<8>,
+    },
+    /// The ball bounced off an obstacle, or the solid wall behind the bricks
+    Bounce {
+        pos: Vector2D<FixedNum<8>>,
+        normal: Vector2D<FixedNum<8>>,
+    },
+    /// The ball knocked a hit off a brick
+    BrickHit {
+        pos: Vector2D<FixedNum<8>>,
+        normal: Vector2D<FixedNum<8>>,
+        speed: FixedNum<8>,
+    },
+    /// The ball went off the edge of the screen on `side`, so that side conceded a point
+    Score { side: Side },
+}
+
+#[derive(Default)]
+pub struct EventSink {
+    events: [Option<GameEvent>; MAX_EVENTS],
+    len: usize,
+}
+
+impl EventSink {
+    pub fn push(&mut self, event: GameEvent) {
+        if let Some(slot) = self.events.get_mut(self.len) {
+            *slot = Some(event);
+            self.len += 1;
+        }
+    }
+
+    /// The events since the last drain, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &GameEvent> {
+        self.events[..self.len].iter().flatten()
+    }
+
+    /// Takes out all the events, oldest first, leaving the sink empty
+    pub fn drain(&mut self) -> impl Iterator<Item = GameEvent> + use<> {
+        let len = core::mem::take(&mut self.len);
+        core::mem::take(&mut self.events)
+            .into_iter()
+            .take(len)
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn full_sink_drops_new_events(_gba: &mut agb::Gba) {
+        let mut events = EventSink::default();
+        for _ in 0..MAX_EVENTS {
+            events.push(GameEvent::Score { side: Side::Left });
+        }
+        events.push(GameEvent::Score { side: Side::Right });
+
+        assert_eq!(events.iter().count(), MAX_EVENTS);
+        assert!(
+            events
+                .drain()
+                .all(|event| event == GameEvent::Score { side: Side::Left })
+        );
+        assert_eq!(events.iter().count(), 0, "draining should empty the sink");
+    }
+}
@@ -0,0 +1,132 @@
+//! The buttons the game reads each frame, copied out of the controller so that something else,
+//! like a replay, can stand in for the player.
+
+use agb::input::{Button, ButtonController};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Input {
+    current: Button,
+    previous: Button,
+}
+
+impl Input {
+    pub fn new(current: Button, previous: Button) -> Self {
+        Self { current, previous }
+    }
+
+    /// The buttons held down this frame
+    pub fn current(&self) -> Button {
+        self.current
+    }
+
+    /// The buttons that were held down the frame before
+    pub fn previous(&self) -> Button {
+        self.previous
+    }
+
+    /// True if any of `keys` are held down
+    pub fn is_pressed(&self, keys: Button) -> bool {
+        self.current.intersects(keys)
+    }
+
+    /// True if any of `keys` went down this frame, matching
+    /// [`ButtonController::is_just_pressed`]
+    pub fn is_just_pressed(&self, keys: Button) -> bool {
+        self.current.intersects(keys) && !self.previous.intersects(keys)
+    }
+}
+
+/// Holds on to the buttons pressed on frames where the game doesn't step, which happens every so
+/// often at the slower game speeds, so that a quick press still counts on the next step there is
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PressLatch {
+    pressed: Button,
+}
+
+impl PressLatch {
+    /// The input for this frame's `steps`, with anything pressed since the last frame that
+    /// stepped counted as just pressed now
+    pub fn latch(&mut self, input: Input, steps: u32) -> Input {
+        self.pressed |= input.current & !input.previous;
+        if steps == 0 {
+            return input;
+        }
+
+        let pressed = core::mem::replace(&mut self.pressed, Button::empty());
+        Input::new(input.current | pressed, input.previous & !pressed)
+    }
+
+    /// Forgets any presses that haven't reached a step yet, so the ones that go to a menu don't
+    /// carry on into the game
+    pub fn clear(&mut self) {
+        self.pressed = Button::empty();
+    }
+}
+
+impl Default for PressLatch {
+    fn default() -> Self {
+        Self {
+            pressed: Button::empty(),
+        }
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new(Button::empty(), Button::empty())
+    }
+}
+
+impl From<&ButtonController> for Input {
+    fn from(bc: &ButtonController) -> Self {
+        // The controller doesn't hand out its raw state, so it's asked about each button in turn
+        let mut input = Self::default();
+        for button in Button::all().iter() {
+            let pressed = bc.is_pressed(button);
+            input.current.set(button, pressed);
+            input.previous.set(
+                button,
+                (pressed && !bc.is_just_pressed(button)) || bc.is_just_released(button),
+            );
+        }
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ball::Ball;
+    use crate::rng::Rng;
+    use crate::timestep::FixedTimestep;
+    use crate::{GameSpeed, Side};
+    use agb::fixnum::{num, vec2};
+
+    #[test_case]
+    fn a_serve_on_a_frame_without_a_step_still_goes(_gba: &mut agb::Gba) {
+        let mut ball = Ball::new(vec2(num!(50), num!(50)), vec2(num!(2), num!(0.5)));
+        ball.serve(&mut Rng::new(1), Side::Right);
+        let mut timestep = FixedTimestep::new();
+        let mut latch = PressLatch::default();
+        let speed = GameSpeed::Half.fraction();
+
+        // Tap A on the first frame the game doesn't step on, then let go
+        let mut pressed = false;
+        for _ in 0..8 {
+            let steps = timestep.scaled(1, speed);
+            let buttons = if !pressed && steps == 0 {
+                pressed = true;
+                Button::A
+            } else {
+                Button::empty()
+            };
+            let input = latch.latch(Input::new(buttons, Button::empty()), steps);
+            for _ in 0..steps {
+                ball.update_serve(&input, false);
+            }
+        }
+
+        assert!(pressed, "half speed should skip a step now and then");
+        assert!(ball.is_in_play(), "the press should serve on the next step");
+    }
+}
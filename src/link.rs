@@ -0,0 +1,423 @@
+//! Two player games over the link cable. The host runs the game as normal and sends the ball,
+//! scores and its paddle to the client every frame, while the client only sends back where its
+//! paddle is.
+//!
+//! agb doesn't wrap the serial port, so this drives the multiplayer mode registers directly. The
+//! words of each packet go back and forth in the serial interrupt, so the game never has to wait
+//! on the other console.
+
+use core::cell::RefCell;
+
+use agb::fixnum::{FixedNum, Vector2D, vec2};
+use agb::interrupt::{Interrupt, InterruptHandler, add_interrupt_handler};
+use critical_section::Mutex;
+
+const SIOMULTI0: *mut u16 = 0x0400_0120 as *mut u16;
+const SIOMULTI1: *mut u16 = 0x0400_0122 as *mut u16;
+const SIOCNT: *mut u16 = 0x0400_0128 as *mut u16;
+const SIOMLT_SEND: *mut u16 = 0x0400_012A as *mut u16;
+const RCNT: *mut u16 = 0x0400_0134 as *mut u16;
+
+/// Multiplayer mode at 115200 baud, with an interrupt at the end of every transfer
+const MULTIPLAYER_MODE: u16 = 0x2000 | 0x4000 | 3;
+/// Set by the host to start a transfer, and stays set until it's finished
+const START: u16 = 1 << 7;
+/// Set if this console is one of the children rather than the parent
+const CHILD: u16 = 1 << 2;
+/// What a console reads for a player that isn't connected
+const NOBODY: u16 = 0xffff;
+
+/// The first word of every packet, so the client can find the start of one
+const MAGIC: u16 = 0x504e;
+/// The second word of every packet says what the rest of it holds, either the state of a game or
+/// nothing at all
+const GAME_PACKET: u16 = 1;
+/// Sent when there's no game going, on the game over screen, so the other console can tell this
+/// one is still there
+const IDLE_PACKET: u16 = 2;
+const PACKET_LEN: usize = 11;
+
+type Packet = [u16; PACKET_LEN];
+
+/// How long the host waits after each transfer for the client to get its next word ready
+const TRANSFER_GAP: u32 = 200;
+/// How long to spin between tries at the handshake, a few frames' worth
+const CONNECT_WAIT: u32 = 100_000;
+/// How many times to try the handshake before giving up, around two seconds
+const CONNECT_ATTEMPTS: u32 = 30;
+/// How many frames in a row can go by without a packet before the other console counts as gone,
+/// half a second
+const MAX_MISSED_FRAMES: u32 = 30;
+
+/// The packets on their way between the consoles, shared with the serial interrupt
+struct Transfers {
+    role: Role,
+    /// The packet to send once the one going now is finished
+    next: Packet,
+    sending: Packet,
+    receiving: Packet,
+    /// How many words of the packet have been swapped so far
+    at: usize,
+    /// The last whole packet to arrive, until it's picked up
+    received: Option<Packet>,
+}
+
+static TRANSFERS: Mutex<RefCell<Transfers>> = Mutex::new(RefCell::new(Transfers {
+    role: Role::Host,
+    next: [0; PACKET_LEN],
+    sending: [0; PACKET_LEN],
+    receiving: [0; PACKET_LEN],
+    at: 0,
+    received: None,
+}));
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Plays the left paddle and runs the game
+    Host,
+    /// Plays the right paddle and shows whatever the host sends it
+    Client,
+}
+
+/// Everything the client needs to draw the game. The ball here is the only one in play, as link
+/// games don't have extra balls.
+pub struct HostState {
+    pub ball_pos: Vector2D<FixedNum<8>>,
+    pub ball_velocity: Vector2D<FixedNum<8>>,
+    pub paddle_y: FixedNum<8>,
+    pub scores: [u16; 2],
+    pub health: [u16; 2],
+    pub countdown: u16,
+}
+
+pub struct ClientState {
+    pub paddle_y: FixedNum<8>,
+}
+
+/// How a frame's exchange went
+pub enum Exchange<T> {
+    Received(T),
+    /// The other console is still there, but it isn't in a game so there's nothing to update
+    Idle,
+    /// Nothing arrived since last frame, or it didn't make sense, so this frame's update is lost
+    Missed,
+    /// Nothing has come back for too long, so the other console has gone
+    Disconnected,
+}
+
+/// Packs a number into a word, keeping 4 bits of fraction. That's still finer than a pixel, and
+/// leaves room for anything on the screen.
+fn to_word(value: FixedNum<8>) -> u16 {
+    (value.to_raw() >> 4) as i16 as u16
+}
+
+fn from_word(word: u16) -> FixedNum<8> {
+    FixedNum::from_raw(i32::from(word as i16) << 4)
+}
+
+/// Two small numbers in one word, saturating at 255
+fn pair_to_word(pair: [u16; 2]) -> u16 {
+    (pair[0].min(255) << 8) | pair[1].min(255)
+}
+
+fn pair_from_word(word: u16) -> [u16; 2] {
+    [word >> 8, word & 0xff]
+}
+
+fn checksum(words: &[u16]) -> u16 {
+    words
+        .iter()
+        .fold(0, |sum: u16, &word| sum.wrapping_add(word))
+}
+
+/// Fills in the magic word, `kind` and checksum around `body`
+fn seal(kind: u16, body: &[u16]) -> Packet {
+    let mut packet = [0; PACKET_LEN];
+    packet[0] = MAGIC;
+    packet[1] = kind;
+    packet[2..2 + body.len()].copy_from_slice(body);
+    packet[PACKET_LEN - 1] = checksum(&packet[..PACKET_LEN - 1]);
+    packet
+}
+
+fn is_intact(packet: &Packet) -> bool {
+    packet[0] == MAGIC && packet[PACKET_LEN - 1] == checksum(&packet[..PACKET_LEN - 1])
+}
+
+/// Whether `packet` is an undamaged game packet
+fn is_game(packet: &Packet) -> bool {
+    is_intact(packet) && packet[1] == GAME_PACKET
+}
+
+impl HostState {
+    fn to_packet(&self) -> Packet {
+        seal(
+            GAME_PACKET,
+            &[
+                to_word(self.ball_pos.x),
+                to_word(self.ball_pos.y),
+                to_word(self.ball_velocity.x),
+                to_word(self.ball_velocity.y),
+                to_word(self.paddle_y),
+                pair_to_word(self.scores),
+                pair_to_word(self.health),
+                self.countdown,
+            ],
+        )
+    }
+
+    fn from_packet(packet: &Packet) -> Option<Self> {
+        if !is_game(packet) {
+            return None;
+        }
+
+        Some(Self {
+            ball_pos: vec2(from_word(packet[2]), from_word(packet[3])),
+            ball_velocity: vec2(from_word(packet[4]), from_word(packet[5])),
+            paddle_y: from_word(packet[6]),
+            scores: pair_from_word(packet[7]),
+            health: pair_from_word(packet[8]),
+            countdown: packet[9],
+        })
+    }
+}
+
+impl ClientState {
+    fn to_packet(&self) -> Packet {
+        seal(GAME_PACKET, &[to_word(self.paddle_y)])
+    }
+
+    fn from_packet(packet: &Packet) -> Option<Self> {
+        if !is_game(packet) {
+            return None;
+        }
+
+        Some(Self {
+            paddle_y: from_word(packet[2]),
+        })
+    }
+}
+
+/// Spins for `count` turns of the loop
+fn spin(count: u32) {
+    for _ in 0..count {
+        core::hint::spin_loop();
+    }
+}
+
+/// Whether the host is in the middle of a transfer
+fn is_transferring() -> bool {
+    // Safety: reading the serial control register has no side effects
+    unsafe { SIOCNT.read_volatile() & START != 0 }
+}
+
+/// Runs at the end of every transfer, on both consoles. It keeps the word that came in and gets
+/// the next one ready, and the host starts the next transfer straight away until the whole packet
+/// has gone.
+fn on_transfer(transfers: &mut Transfers) {
+    // Safety: these are the serial registers, which only the link uses
+    let word = unsafe {
+        match transfers.role {
+            Role::Host => SIOMULTI1.read_volatile(),
+            Role::Client => SIOMULTI0.read_volatile(),
+        }
+    };
+    // The client isn't there, so the host gives up on the packet. It tries again next frame.
+    if word == NOBODY {
+        transfers.at = 0;
+        return;
+    }
+    // The client might have started listening part way through a packet, so it skips ahead to
+    // the start of the next one. The host gets a garbled packet for that frame.
+    if transfers.at == 0 && word != MAGIC && transfers.role == Role::Client {
+        return;
+    }
+
+    transfers.receiving[transfers.at] = word;
+    transfers.at += 1;
+    if transfers.at == PACKET_LEN {
+        transfers.received = Some(transfers.receiving);
+        transfers.sending = transfers.next;
+        transfers.at = 0;
+    }
+
+    // Safety: as above
+    unsafe { SIOMLT_SEND.write_volatile(transfers.sending[transfers.at]) };
+    if transfers.role == Role::Host && transfers.at != 0 {
+        spin(TRANSFER_GAP);
+        // Safety: as above
+        unsafe { SIOCNT.write_volatile(MULTIPLAYER_MODE | START) };
+    }
+}
+
+pub struct Link {
+    role: Role,
+    /// Keeps the transfers going, they stop as soon as this is dropped
+    _transfers: InterruptHandler,
+    /// How many frames in a row have gone by without a packet from the other console
+    missed: u32,
+}
+
+impl Link {
+    /// Looks for another console on the link cable, waiting a couple of seconds for it to answer.
+    /// Whichever console the cable makes the parent becomes the host.
+    pub fn connect() -> Option<Self> {
+        // Safety: these are the serial registers, which nothing else uses
+        unsafe {
+            RCNT.write_volatile(0);
+            SIOCNT.write_volatile(MULTIPLAYER_MODE);
+        }
+
+        // Safety: reading the serial control register has no side effects
+        let role = if unsafe { SIOCNT.read_volatile() } & CHILD != 0 {
+            Role::Client
+        } else {
+            Role::Host
+        };
+        critical_section::with(|cs| {
+            let mut transfers = TRANSFERS.borrow_ref_mut(cs);
+            transfers.role = role;
+            transfers.at = 0;
+            transfers.received = None;
+        });
+
+        // Safety: only touches the transfers, so doesn't allocate
+        let transfers = unsafe {
+            add_interrupt_handler(Interrupt::Serial, |cs| {
+                on_transfer(&mut TRANSFERS.borrow_ref_mut(cs));
+            })
+        };
+        let mut link = Self {
+            role,
+            _transfers: transfers,
+            missed: 0,
+        };
+
+        // Both sides send an idle packet, and one coming back whole tells each of them there's
+        // really somebody there
+        for _ in 0..CONNECT_ATTEMPTS {
+            link.exchange(seal(IDLE_PACKET, &[]));
+            spin(CONNECT_WAIT);
+            if link.exchange(seal(IDLE_PACKET, &[])).is_some() {
+                return Some(link);
+            }
+        }
+
+        None
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Hands `packet` over to go to the other console, and picks up the last packet that came
+    /// back whole, if one has since the last call. Nothing here waits for the transfers, which go
+    /// on in the serial interrupt, so anything sent now gets its answer on a later call.
+    fn exchange(&mut self, packet: Packet) -> Option<Packet> {
+        let received = critical_section::with(|cs| {
+            let mut transfers = TRANSFERS.borrow_ref_mut(cs);
+            transfers.next = packet;
+
+            // Between packets the new one can go straight away. Otherwise it waits for the one
+            // going now to finish. The host starts every packet, and the client's is ready for
+            // whenever it does.
+            if transfers.at == 0 && !is_transferring() {
+                transfers.sending = packet;
+                // Safety: these are the serial registers, which only the link uses
+                unsafe {
+                    SIOMLT_SEND.write_volatile(packet[0]);
+                    if self.role == Role::Host {
+                        SIOCNT.write_volatile(MULTIPLAYER_MODE | START);
+                    }
+                }
+            }
+
+            transfers.received.take()
+        });
+
+        received.filter(is_intact)
+    }
+
+    /// Swaps packets like `exchange`, reading the one that came back with `parse`. Once nothing
+    /// has come back for `MAX_MISSED_FRAMES` in a row the other console is taken to have gone.
+    fn exchange_as<T>(
+        &mut self,
+        packet: Packet,
+        parse: impl FnOnce(&Packet) -> Option<T>,
+    ) -> Exchange<T> {
+        let exchange = match self.exchange(packet) {
+            Some(packet) if packet[1] == IDLE_PACKET => Exchange::Idle,
+            Some(packet) => parse(&packet).map_or(Exchange::Missed, Exchange::Received),
+            None => Exchange::Missed,
+        };
+
+        if !matches!(exchange, Exchange::Missed) {
+            self.missed = 0;
+        } else if self.missed < MAX_MISSED_FRAMES {
+            self.missed += 1;
+        } else {
+            return Exchange::Disconnected;
+        }
+        exchange
+    }
+
+    /// Sends the state of the game to the client and gets its paddle back
+    pub fn exchange_host(&mut self, state: &HostState) -> Exchange<ClientState> {
+        self.exchange_as(state.to_packet(), ClientState::from_packet)
+    }
+
+    /// Sends the client's paddle to the host and gets the state of the game back
+    pub fn exchange_client(&mut self, state: &ClientState) -> Exchange<HostState> {
+        self.exchange_as(state.to_packet(), HostState::from_packet)
+    }
+
+    /// Keeps the link going while there's no game to swap, on the game over screen. Returns
+    /// false once the other console has gone.
+    pub fn keep_alive(&mut self) -> bool {
+        let exchange = self.exchange_as(seal(IDLE_PACKET, &[]), |_| Some(()));
+        !matches!(exchange, Exchange::Disconnected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agb::fixnum::num;
+
+    #[test_case]
+    fn host_state_survives_the_trip(_gba: &mut agb::Gba) {
+        let state = HostState {
+            ball_pos: vec2(num!(123.5), num!(-4.25)),
+            ball_velocity: vec2(num!(-2.0625), num!(1.5)),
+            paddle_y: num!(96),
+            scores: [10, 300],
+            health: [3, 0],
+            countdown: 117,
+        };
+
+        let received = HostState::from_packet(&state.to_packet()).unwrap();
+        assert_eq!(received.ball_pos, state.ball_pos);
+        assert_eq!(received.ball_velocity, state.ball_velocity);
+        assert_eq!(received.paddle_y, state.paddle_y);
+        assert_eq!(received.scores, [10, 255], "scores saturate to fit");
+        assert_eq!(received.health, state.health);
+        assert_eq!(received.countdown, state.countdown);
+    }
+
+    #[test_case]
+    fn damaged_packets_are_rejected(_gba: &mut agb::Gba) {
+        let mut packet = ClientState { paddle_y: num!(40) }.to_packet();
+        assert!(ClientState::from_packet(&packet).is_some());
+
+        packet[2] ^= 1;
+        assert!(ClientState::from_packet(&packet).is_none());
+    }
+
+    #[test_case]
+    fn idle_packets_are_not_game_state(_gba: &mut agb::Gba) {
+        let idle = seal(IDLE_PACKET, &[]);
+        assert!(is_intact(&idle));
+        assert!(ClientState::from_packet(&idle).is_none());
+        assert!(HostState::from_packet(&idle).is_none());
+    }
+}
@@ -15,358 +15,2806 @@
 // until you declare the extern crate. `agb` provides an allocator so it will all work
 extern crate alloc;
 
-use agb::display::object::Object;
-use agb::display::tiled::{RegularBackground, RegularBackgroundSize, TileFormat, VRAM_MANAGER};
+mod announcer;
+mod ball;
+mod benchmark;
+mod brick;
+mod collision;
+mod controller;
+mod event;
+mod input;
+mod link;
+mod music;
+mod obstacle;
+mod paddle;
+mod persistence;
+mod quad;
+mod replay;
+mod rng;
+mod snapshot;
+mod sprite_budget;
+mod theme;
+mod timestep;
+mod wind;
+
+use agb::display::object::{Object, Tag};
+use agb::display::tile_data::TileData;
+use agb::display::tiled::{RegularBackground, RegularBackgroundSize, TileFormat};
 use agb::display::{GraphicsFrame, Priority, WIDTH};
-use agb::fixnum::{FixedNum, Number, Rect, Vector2D, num, vec2};
+use agb::fixnum::{FixedNum, Num, Rect, Vector2D, num, vec2};
 use agb::include_wav;
-use agb::input::{Button, ButtonController};
+use agb::input::Button;
 use agb::sound::mixer::{Frequency, Mixer, SoundChannel, SoundData};
 use agb::{include_aseprite, include_background_gfx};
 use agb_tracker::{Track, Tracker, include_xm};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use announcer::{Announcer, VoiceLine};
+use ball::{BALL_RADIUS, Ball, EdgeMode, LARGE_BALL_RADIUS, Opponent, collide_balls, full_court};
+use benchmark::BENCHMARK_MATCHES;
+use brick::Brick;
+use collision::{Circle, Touches};
+use controller::{AiController, HumanController};
+use event::{EventSink, GameEvent};
+use input::{Input, PressLatch};
+use link::{ClientState, Exchange, HostState, Link, Role};
+use music::MusicMixer;
+use obstacle::{Obstacle, ObstacleLayout};
+use paddle::{Difficulty, PADDLE_INSET, PADDLE_MAX_SPEED, Paddle, start_pos_in};
+use quad::FourPlayerGame;
+use replay::{ReplaySetup, Replays};
+use rng::Rng;
+use snapshot::MatchSnapshot;
+use sprite_budget::{Decoration, ObjectSink, SpriteBudget};
+use theme::{Palettes, Theme};
+use timestep::{FixedTimestep, seconds_for_steps, steps_for_seconds};
+use wind::Wind;
 
 include_background_gfx!(
     mod background,
     PLAY_FIELD => deduplicate "gfx/background.aseprite",
     GAME_OVER => deduplicate "gfx/game_over.aseprite",
+    TITLE => deduplicate "gfx/title.aseprite",
+    OPTIONS => deduplicate "gfx/options.aseprite",
 );
 
 include_aseprite!(
     mod sprites,
     "gfx/cpu.aseprite",
     "gfx/sprites.aseprite",
+    "gfx/cpu_paddle.aseprite",
+    "gfx/paddle_horizontal.aseprite",
     "gfx/health.aseprite",
-    "gfx/player.aseprite"
+    "gfx/player.aseprite",
+    "gfx/digits.aseprite",
+    "gfx/deuce.aseprite",
+    "gfx/overtime.aseprite",
+    "gfx/best.aseprite",
+    "gfx/power_ups.aseprite",
+    "gfx/paused.aseprite",
+    "gfx/pause_menu.aseprite",
+    "gfx/go.aseprite",
+    "gfx/wins.aseprite",
+    "gfx/menu.aseprite",
+    "gfx/trail.aseprite",
+    "gfx/flash.aseprite",
+    "gfx/particle.aseprite",
+    "gfx/obstacle.aseprite",
+    "gfx/brick.aseprite",
+    "gfx/wind.aseprite",
+    "gfx/themes.aseprite",
+    "gfx/big_ball.aseprite",
+    "gfx/aim.aseprite",
+    "gfx/charge.aseprite",
+    "gfx/landing.aseprite",
+    "gfx/option_labels.aseprite",
+    "gfx/option_values.aseprite",
+    "gfx/stats.aseprite",
+    "gfx/continue.aseprite"
 );
 
 static BALL_PADDLE_HIT: SoundData = include_wav!("sfx/ball-paddle-hit.wav");
 static BGM: Track = include_xm!("sfx/bgm.xm");
 static WALL_HIT: SoundData = include_wav!("sfx/wall-hit.wav");
 
-fn play_sound(mixer: &mut Mixer, sound: SoundData) {
-    let hit_sound = SoundChannel::new(sound);
-    mixer.play_sound(hit_sound);
+// `num!` can't be used to define constants, so this does the same conversion at compile time
+const fn fixed(value: f32) -> FixedNum<8> {
+    FixedNum::from_raw((value * 256.) as i32)
 }
 
-struct Circle<T: Number> {
-    pos: Vector2D<T>,
-    radius: T,
+/// How much each press of left or right in the options changes the volume by
+const VOLUME_STEP: FixedNum<8> = fixed(0.125);
+/// The numbers of lives the options let you start with
+const LIVES_CHOICES: [u16; 3] = [1, 3, 5];
+/// How hard gravity pulls the balls down every frame when it's turned on, about 1/50th of a pixel
+/// per frame. That's enough for a shot across the court to drop by a good third of the screen.
+const GRAVITY_PULL: FixedNum<8> = FixedNum::from_raw(5);
+
+/// Everything the player can change on the options screen, which is kept in the save
+#[derive(Clone, Copy)]
+pub struct Settings {
+    /// How good the CPU is on the right paddle
+    pub difficulty: Difficulty,
+    /// What it takes to win a game
+    pub win_condition: WinCondition,
+    /// How many misses each paddle can take when playing for lives, one of [`LIVES_CHOICES`]
+    pub lives: u16,
+    /// From 0 for silent up to 1 for full volume
+    pub volume: FixedNum<8>,
+    pub muted: bool,
+    pub theme: Theme,
+    pub game_speed: GameSpeed,
+    /// Plays with a bigger ball that's easier to see
+    pub large_ball: bool,
+    /// Marks where the ball's going to reach the player's paddle, for beginners
+    pub assist: bool,
+    /// Ends games played for points early once somebody's miles ahead
+    pub mercy_rule: bool,
+    /// Whether the ball bounces off the top and bottom or wraps around to the other side
+    pub edge_mode: EdgeMode,
+    /// Plays with a paddle guarding every edge of the screen, all of them but the player's
+    /// played by the CPU
+    pub four_player: bool,
+    /// Has the CPU ease off when it's ahead and try harder when it's behind, whatever the
+    /// difficulty
+    pub rubber_band: bool,
+    /// Launches extra balls every so often
+    pub multiball: bool,
+    /// Which blocks, if any, stand in the middle of the field
+    pub obstacles: ObstacleLayout,
+    /// Pulls the balls down so their shots arc
+    pub gravity: bool,
+    /// Has gusts of wind blow the balls sideways every so often
+    pub wind: bool,
+    /// Runs the game at double speed, for practice or for getting to the end of a game quickly.
+    /// It's kept apart from `game_speed`, which is there to make the ball easier to follow.
+    pub turbo: bool,
+    /// Runs the game at half speed to practise against, the other way from `turbo`. Only one of
+    /// them is on at a time.
+    pub slow_practice: bool,
 }
 
-trait Touches<T> {
-    fn touches(&self, rhs: T) -> bool;
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Medium,
+            win_condition: WinCondition::Score,
+            lives: 3,
+            volume: num!(1),
+            muted: false,
+            theme: Theme::Classic,
+            game_speed: GameSpeed::Full,
+            large_ball: false,
+            assist: false,
+            mercy_rule: true,
+            edge_mode: EdgeMode::Bounce,
+            four_player: false,
+            rubber_band: false,
+            multiball: false,
+            obstacles: ObstacleLayout::None,
+            gravity: false,
+            wind: false,
+            turbo: false,
+            slow_practice: false,
+        }
+    }
 }
 
-impl<T: Number> Circle<T> {
-    pub fn new(pos: Vector2D<T>, radius: T) -> Self {
-        Self { pos, radius }
+impl Settings {
+    /// The volume to actually play sounds at, taking the mute into account
+    pub fn effective_volume(&self) -> FixedNum<8> {
+        if self.muted { num!(0) } else { self.volume }
     }
-    pub fn centre(&self) -> Vector2D<T> {
-        self.pos + vec2(self.radius, self.radius)
+
+    /// Turns the volume up or down by `steps` of [`VOLUME_STEP`], staying between silent and full
+    pub fn change_volume(&mut self, steps: i32) {
+        self.volume = (self.volume + VOLUME_STEP * steps)
+            .max(num!(0))
+            .min(num!(1));
     }
-}
 
-impl Touches<Rect<FixedNum<8>>> for Circle<FixedNum<8>> {
-    fn touches(&self, rect: Rect<FixedNum<8>>) -> bool {
-        // which edge is closest;
-        let test_x = match self.centre().x {
-            cx if cx < rect.top_left().x => rect.top_left().x,
-            cx if cx > rect.bottom_right().x => rect.bottom_right().x,
-            cx => cx,
-        };
-        let test_y = match self.centre().y {
-            cy if cy < rect.top_left().y => rect.top_left().y,
-            cy if cy > rect.bottom_left().y => rect.bottom_left().y,
-            cy => cy,
-        };
+    /// Moves `steps` along [`LIVES_CHOICES`], stopping at either end
+    pub fn change_lives(&mut self, steps: i32) {
+        let index = LIVES_CHOICES
+            .iter()
+            .position(|&lives| lives == self.lives)
+            .unwrap_or(0) as i32;
+        let index = (index + steps).clamp(0, LIVES_CHOICES.len() as i32 - 1);
+        self.lives = LIVES_CHOICES[index as usize];
+    }
+
+    /// How many steps the game takes for each one at full speed, doubled by turbo and halved by
+    /// slow practice
+    pub fn speed(&self) -> FixedNum<8> {
+        let speed = self.game_speed.fraction();
+        if self.turbo {
+            speed * 2
+        } else if self.slow_practice {
+            speed / 2
+        } else {
+            speed
+        }
+    }
 
-        let dist_x = self.centre().x - test_x;
-        let dist_y = test_y - self.centre().y;
-        let dist = ((dist_x * dist_x) + (dist_y * dist_y)).sqrt();
+    pub fn ball_radius(&self) -> FixedNum<8> {
+        if self.large_ball {
+            LARGE_BALL_RADIUS
+        } else {
+            BALL_RADIUS
+        }
+    }
 
-        dist <= self.radius
+    /// The rules for a new match, which are the normal ones apart from what's been changed here
+    pub fn game_config(&self) -> GameConfig {
+        let normal = GameConfig::default();
+        GameConfig {
+            win_condition: self.win_condition,
+            ball_radius: self.ball_radius(),
+            edge_mode: self.edge_mode,
+            starting_health: self.lives,
+            mercy_margin: normal.mercy_margin.filter(|_| self.mercy_rule),
+            ball_velocity: normal.ball_velocity * self.difficulty.ball_speed(),
+            rubber_band: self.rubber_band,
+            multiball: self.multiball,
+            obstacles: self.obstacles,
+            gravity: if self.gravity {
+                vec2(num!(0), GRAVITY_PULL)
+            } else {
+                normal.gravity
+            },
+            wind: self.wind,
+            ..normal
+        }
     }
 }
 
-pub struct Ball {
-    pos: Vector2D<FixedNum<8>>,
-    velocity: Vector2D<FixedNum<8>>,
+/// How fast the game runs, for players who find the ball too quick to follow. Everything slows
+/// down together, so the game plays the same just with more time to react.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GameSpeed {
+    Half,
+    ThreeQuarters,
+    Full,
 }
 
-impl Ball {
-    pub fn new(pos: Vector2D<FixedNum<8>>, velocity: Vector2D<FixedNum<8>>) -> Self {
-        Self { pos, velocity }
+const GAME_SPEEDS: [GameSpeed; 3] = [GameSpeed::Half, GameSpeed::ThreeQuarters, GameSpeed::Full];
+
+impl GameSpeed {
+    /// Where the speed is in the list, which is also how it's saved
+    pub fn index(self) -> u8 {
+        GAME_SPEEDS
+            .iter()
+            .position(|&speed| speed == self)
+            .unwrap_or(0) as u8
     }
 
-    pub fn update(
-        &mut self,
-        paddle_a: &mut Paddle<P1>,
-        paddle_b: &mut Paddle<P2>,
-        mixer: &mut Mixer,
-    ) {
-        // Speculatively move the ball, we'll update the velocity if this causes it to intersect with either the
-        // edge of the map or a paddle.
-        let potential_ball_pos = self.pos + self.velocity;
-
-        let ball_mask = Circle::new(potential_ball_pos, num!(8));
-        if ball_mask.touches(paddle_a.collision_rect()) {
-            self.velocity.x = self.velocity.x.abs();
-            let y_difference = (ball_mask.centre().y - paddle_a.collision_rect().centre().y) / 32;
-            self.velocity.y += y_difference;
-            play_sound(mixer, BALL_PADDLE_HIT);
-        }
-
-        if ball_mask.touches(paddle_b.collision_rect()) {
-            self.velocity.x = -self.velocity.x.abs();
-            let y_difference = (ball_mask.centre().y - paddle_b.collision_rect().centre().y) / 32;
-            self.velocity.y -= y_difference;
-            play_sound(mixer, BALL_PADDLE_HIT);
-        }
-
-        // We check if the ball reaches the edge of the screen and reverse it's direction
-        if potential_ball_pos.y <= num!(0)
-            || potential_ball_pos.y >= num!(agb::display::HEIGHT - 16)
-        {
-            self.velocity.y *= -1;
-            play_sound(mixer, WALL_HIT);
-        }
+    pub fn from_index(index: u8) -> Option<Self> {
+        GAME_SPEEDS.get(usize::from(index)).copied()
+    }
 
-        if potential_ball_pos.x <= num!(0) {
-            paddle_a.health -= 1;
-            self.reset();
-        }
-        if potential_ball_pos.x >= num!(agb::display::WIDTH - 16) {
-            paddle_b.health -= 1;
-            self.reset();
+    /// The speed `steps` along the list from this one, stopping at either end
+    pub fn change(self, steps: i32) -> Self {
+        let index = (i32::from(self.index()) + steps).clamp(0, GAME_SPEEDS.len() as i32 - 1);
+        GAME_SPEEDS[index as usize]
+    }
+
+    /// The fraction of the steps the game takes at this speed
+    pub fn fraction(self) -> FixedNum<8> {
+        match self {
+            GameSpeed::Half => num!(0.5),
+            GameSpeed::ThreeQuarters => num!(0.75),
+            GameSpeed::Full => num!(1),
         }
+    }
 
-        self.pos += self.velocity;
+    fn percent(self) -> u32 {
+        (self.fraction() * 100).floor() as u32
     }
-    pub fn reset(&mut self) {
-        self.pos = vec2(num!(50), num!(50));
-        self.velocity = vec2(num!(2), num!(0.5));
+}
+
+/// The playback speeds of the hit sound for a stationary ball and one at full speed
+const MIN_HIT_PITCH: FixedNum<8> = fixed(0.75);
+const MAX_HIT_PITCH: FixedNum<8> = fixed(1.5);
+
+/// Where on the screen a sound happened, and how fast to play it back
+#[derive(Clone, Copy)]
+pub struct SoundCue {
+    x: FixedNum<8>,
+    pitch: FixedNum<8>,
+}
+
+impl SoundCue {
+    /// A sound at normal pitch that happened at `x`
+    pub fn at(x: FixedNum<8>) -> Self {
+        Self { x, pitch: num!(1) }
     }
 
-    pub fn show(&self, frame: &mut GraphicsFrame) {
-        let pos = self.pos.round();
-        Object::new(sprites::BALL.sprite(0))
-            .set_pos(pos)
-            .set_priority(Priority::P1)
-            .show(frame);
+    /// A ball being hit at `x`, pitched higher the closer `speed` is to the ball's top speed
+    fn hit(x: FixedNum<8>, speed: FixedNum<8>) -> Self {
+        let pitch = (MIN_HIT_PITCH + (MAX_HIT_PITCH - MIN_HIT_PITCH) * speed)
+            .max(MIN_HIT_PITCH)
+            .min(MAX_HIT_PITCH);
+        Self { x, pitch }
     }
 }
 
-const P1: bool = true;
-const P2: bool = false;
+/// Plays `sound` panned towards where it happened on the screen
+fn play_sound(mixer: &mut Mixer, sound: SoundData, volume: FixedNum<8>, cue: SoundCue) {
+    if volume == num!(0) {
+        return;
+    }
+
+    // Map the left edge of the screen to -1 and the right edge to 1
+    let panning = (cue.x * 2 / WIDTH - num!(1)).max(num!(-1)).min(num!(1));
 
-pub struct Paddle<const PLAYER: bool> {
-    pos: Vector2D<FixedNum<8>>,
-    pub health: u16,
+    let mut hit_sound = SoundChannel::new(sound);
+    hit_sound
+        .volume(Num::<i16, 8>::from_raw(volume.to_raw() as i16))
+        .panning(Num::<i16, 8>::from_raw(panning.to_raw() as i16))
+        .playback(Num::<u32, 8>::from_raw(cue.pitch.to_raw() as u32));
+    mixer.play_sound(hit_sound);
 }
 
-impl<const PLAYER: bool> Paddle<PLAYER> {
-    pub fn new(start: Vector2D<FixedNum<8>>, health: u16) -> Self {
-        Self { pos: start, health }
+/// Moves the music on by a frame at the volume from the settings, stopping it while it's silent
+fn play_music(tracker: &mut Tracker, mixer: &mut Mixer, settings: &Settings) {
+    let volume = settings.effective_volume();
+    if volume == num!(0) {
+        tracker.stop(mixer);
+    } else {
+        tracker.step(&mut MusicMixer::new(mixer, volume));
     }
+}
 
-    pub fn move_by(&mut self, y: FixedNum<8>) {
-        self.pos.y = (self.pos.y + y)
-            .max(num!(0))
-            .min(num!(agb::display::HEIGHT - 48));
+/// How far the screen shakes when the ball hits a wall at full speed, slower balls shake it less
+const MAX_WALL_SHAKE: FixedNum<8> = fixed(2.);
+/// How far the screen shakes when a point is scored
+const SCORE_SHAKE: FixedNum<8> = fixed(4.);
+/// How much of the shake is left after each frame
+const SHAKE_DECAY: FixedNum<8> = fixed(0.75);
+
+/// Jiggles the play field background about for a few frames. Only the background moves, so the
+/// HUD stays readable and nothing drifts away from where it collides.
+#[derive(Default)]
+pub struct ScreenShake {
+    /// The most the background can currently be moved by in any direction
+    magnitude: FixedNum<8>,
+}
+
+impl ScreenShake {
+    /// Starts a shake of `magnitude` pixels, unless a bigger one is already going
+    pub fn trigger(&mut self, magnitude: FixedNum<8>) {
+        self.magnitude = self.magnitude.max(magnitude);
     }
 
-    pub fn set_pos(&mut self, pos: Vector2D<FixedNum<8>>) {
-        self.pos = pos;
+    /// Moves the shake on by a frame, returning how far to offset the background
+    pub fn update(&mut self, rng: &mut Rng) -> Vector2D<i32> {
+        let range = self.magnitude.floor();
+        if range <= 0 {
+            self.magnitude = num!(0);
+            return vec2(0, 0);
+        }
+
+        self.magnitude *= SHAKE_DECAY;
+
+        let range = FixedNum::new(range);
+        let mut jitter = || rng.range(-range, range + num!(1)).floor();
+        vec2(jitter(), jitter())
     }
-    pub fn collision_rect(&self) -> Rect<FixedNum<8>> {
-        let pos = self.pos + vec2(num!(4), num!(4));
-        Rect::new(pos, vec2(num!(10), num!(40)))
-    }
-    fn _update(&mut self, up_pressed: bool, down_pressed: bool) {
-        let y_change = match (up_pressed, down_pressed) {
-            (true, false) => num!(-2),
-            (false, true) => num!(2),
-            (false, false) | (true, true) => num!(0),
-        };
-        self.move_by(y_change);
+}
+
+/// The most particles that can be flying about at once, which keeps them within the object limit
+const MAX_PARTICLES: usize = 16;
+/// How many particles spray out of each collision
+const PARTICLES_PER_HIT: usize = 4;
+/// How many frames a particle lasts for
+const PARTICLE_LIFETIME: u8 = 16;
+
+#[derive(Clone, Copy, Default)]
+struct Particle {
+    pos: Vector2D<FixedNum<8>>,
+    velocity: Vector2D<FixedNum<8>>,
+    /// Frames left before the particle disappears, with 0 meaning the slot is free
+    lifetime: u8,
+}
+
+/// A fixed size pool of little sparks thrown out by collisions
+pub struct Particles {
+    pool: [Particle; MAX_PARTICLES],
+    /// Where the next particle goes. If the pool is full this is the oldest one, so it's the
+    /// one that gets replaced.
+    next: usize,
+    /// The particles get their own generator, so how many sprays there are doesn't change the
+    /// game's random numbers
+    rng: Rng,
+}
+
+impl Particles {
+    pub fn new(rng: Rng) -> Self {
+        Self {
+            pool: [Particle::default(); MAX_PARTICLES],
+            next: 0,
+            rng,
+        }
     }
-    fn _show_health(&self, mut from: Vector2D<i32>, frame: &mut GraphicsFrame) {
-        for i in 0..3 {
-            let heart_frame = if i < self.health.into() { 0 } else { 1 };
 
-            Object::new(sprites::HEART.sprite(heart_frame))
-                .set_pos(from)
-                .show(frame);
+    /// Sprays out a few particles from `pos`, roughly in the direction of `normal`
+    pub fn emit(&mut self, pos: Vector2D<FixedNum<8>>, normal: Vector2D<FixedNum<8>>) {
+        let tangent = vec2(normal.y, -normal.x);
+
+        for _ in 0..PARTICLES_PER_HIT {
+            let speed = self.rng.range(num!(1), num!(2));
+            let spread = self.rng.range(num!(-1), num!(1));
+
+            self.pool[self.next] = Particle {
+                pos,
+                velocity: normal * speed + tangent * spread,
+                lifetime: PARTICLE_LIFETIME,
+            };
+            self.next = (self.next + 1) % MAX_PARTICLES;
+        }
+    }
 
-            from.x += 8;
+    pub fn update(&mut self) {
+        for particle in self
+            .pool
+            .iter_mut()
+            .filter(|particle| particle.lifetime > 0)
+        {
+            particle.pos += particle.velocity;
+            particle.lifetime -= 1;
         }
     }
-    fn _show(&self, frame: &mut GraphicsFrame, h_flip: bool) {
-        let pos = self.pos.round();
 
-        Object::new(sprites::PADDLE_END.sprite(0))
-            .set_pos(pos)
-            .set_priority(Priority::P1)
-            .set_hflip(h_flip)
-            .show(frame);
-        Object::new(sprites::PADDLE_MID.sprite(0))
-            .set_pos(pos + vec2(0, 16))
-            .set_priority(Priority::P1)
-            .set_hflip(h_flip)
-            .show(frame);
-        Object::new(sprites::PADDLE_END.sprite(0))
-            .set_pos(pos + vec2(0, 32))
-            .set_priority(Priority::P1)
-            .set_hflip(h_flip)
-            .set_vflip(true)
-            .show(frame);
+    pub fn show(&self, budget: &mut SpriteBudget) {
+        for particle in self.pool.iter().filter(|particle| particle.lifetime > 0) {
+            // Particles dim for the second half of their life
+            let sprite = if particle.lifetime > PARTICLE_LIFETIME / 2 {
+                0
+            } else {
+                1
+            };
+            let mut spark = Object::new(sprites::PARTICLE.sprite(sprite));
+            spark
+                .set_pos(particle.pos.round())
+                .set_priority(Priority::P2);
+            budget.decorate(Decoration::Particle, spark);
+        }
     }
 }
 
-impl Paddle<P1> {
-    pub fn show(&self, frame: &mut GraphicsFrame) {
-        self._show(frame, false);
+/// Starts the music again from the beginning. The old tracker's channels are stopped first,
+/// otherwise they'd be left ringing in the mixer with nothing to ever stop them.
+fn restart_music(tracker: &mut Tracker, mixer: &mut Mixer) {
+    tracker.stop(mixer);
+    *tracker = Tracker::new(&BGM);
+}
+
+/// How many copies of the same sound can start in one frame. Any more than this are dropped,
+/// and the ones that are left share the volume of one, so 1 plays each sound just the once.
+const MAX_SOUND_INSTANCES: usize = 2;
+
+/// The first few times one sound was asked for during a frame
+#[derive(Default)]
+struct SoundCues {
+    cues: [Option<SoundCue>; MAX_SOUND_INSTANCES],
+    len: usize,
+}
+
+impl SoundCues {
+    fn push(&mut self, cue: SoundCue) {
+        if let Some(slot) = self.cues.get_mut(self.len) {
+            *slot = Some(cue);
+            self.len += 1;
+        }
     }
-    pub fn show_health(&self, frame: &mut GraphicsFrame) {
-        let mut top_left = vec2(3, 4);
 
-        // Display the text `PLayer:`
-        for i in 0..4 {
-            Object::new(sprites::PLAYER.sprite(i))
-                .set_pos(top_left)
-                .show(frame);
-            top_left.x += 8;
+    fn play(&self, mixer: &mut Mixer, sound: SoundData, volume: FixedNum<8>) {
+        if self.len == 0 {
+            return;
         }
 
-        self._show_health(top_left + vec2(3, 0), frame);
+        let volume = volume / self.len as i32;
+        for &cue in self.cues.iter().flatten() {
+            play_sound(mixer, sound, volume, cue);
+        }
     }
-    pub fn update(&mut self, bc: &mut ButtonController) {
-        self._update(bc.is_pressed(Button::UP), bc.is_pressed(Button::DOWN));
+}
+
+/// Collects the sound effects asked for during a frame, so that several balls bouncing at the
+/// same time don't stack the same sound up into a distorted mess. That goes for every step of the
+/// frame too, so the sounds don't double up when the game takes more than one.
+#[derive(Default)]
+pub struct FrameSounds {
+    ball_paddle_hit: SoundCues,
+    wall_hit: SoundCues,
+    /// Whatever the announcer had to say, from the middle of the screen
+    voice: Option<VoiceLine>,
+}
+
+impl FrameSounds {
+    pub fn play(&mut self, mixer: &mut Mixer, volume: FixedNum<8>) {
+        self.ball_paddle_hit.play(mixer, BALL_PADDLE_HIT, volume);
+        self.wall_hit.play(mixer, WALL_HIT, volume);
+        if let Some(line) = self.voice {
+            play_sound(mixer, line.sound(), volume, SoundCue::at(num!(WIDTH / 2)));
+        }
+
+        *self = Self::default();
     }
 }
 
-impl Paddle<P2> {
-    pub fn show(&self, frame: &mut GraphicsFrame) {
-        self._show(frame, true);
+/// Shows how long is left of a `countdown` of [`COUNTDOWN_LENGTH`] frames, then GO just before
+/// it runs out
+fn show_countdown(countdown: u16, frame: &mut impl ObjectSink) {
+    if countdown == 0 {
+        return;
     }
-    pub fn show_health(&self, frame: &mut GraphicsFrame) {
-        let mut top_left = vec2(WIDTH - (8 * 5 + 3 * 2), 4);
 
+    let y = agb::display::HEIGHT / 2 - 24;
+    let step = (countdown - 1) / COUNTDOWN_STEP;
+    if step > 0 {
+        show_number(step.into(), vec2(WIDTH / 2 + 4, y), frame);
+    } else {
         for i in 0..2 {
-            Object::new(sprites::CPU.sprite(i))
-                .set_pos(top_left)
-                .show(frame);
-            top_left.x += 8;
+            frame.show(
+                Object::new(sprites::GO.sprite(i)).set_pos(vec2(WIDTH / 2 - 8 + 8 * i as i32, y)),
+            );
         }
+    }
+}
+
+/// Draws `value` in decimal with its top right corner at `pos`, so numbers are right aligned
+fn show_number(value: u32, pos: Vector2D<i32>, frame: &mut impl ObjectSink) {
+    show_number_with(&sprites::DIGITS, value, pos, frame);
+}
+
+/// Like [`show_number`], but using a different set of digits, e.g. [`sprites::LIGHT_DIGITS`]
+/// for dark backgrounds. Returns the x position of the number's left edge.
+fn show_number_with(
+    digits: &Tag,
+    mut value: u32,
+    pos: Vector2D<i32>,
+    frame: &mut impl ObjectSink,
+) -> i32 {
+    let mut x = pos.x;
+
+    // Peel off the digits from least significant to most, always drawing at least one so 0 shows
+    loop {
+        x -= 8;
+        frame.show(Object::new(digits.sprite((value % 10) as usize)).set_pos(vec2(x, pos.y)));
 
-        self._show_health(top_left + vec2(3, 0), frame);
+        value /= 10;
+        if value == 0 {
+            break;
+        }
     }
-    pub fn update(&mut self, bc: &mut ButtonController) {
-        self._update(bc.is_pressed(Button::A), bc.is_pressed(Button::B));
+
+    x
+}
+
+/// Like [`show_number`], with a minus sign in front of negative numbers
+fn show_signed_number(value: i32, pos: Vector2D<i32>, frame: &mut impl ObjectSink) {
+    let left = show_number_with(&sprites::DIGITS, value.unsigned_abs(), pos, frame);
+    if value < 0 {
+        frame.show(Object::new(sprites::MINUS.sprite(0)).set_pos(vec2(left - 8, pos.y)));
     }
 }
 
-pub struct GamePlay {
-    bg: RegularBackground,
-    ball: Ball,
-    paddle_a: Paddle<P1>,
-    paddle_b: Paddle<P2>,
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PowerUpKind {
+    /// Makes the paddle that hit the ball bigger for a while
+    EnlargePaddle,
+    /// Makes the other paddle smaller for a while
+    ShrinkOpponent,
+    /// Takes the speed off every ball in play
+    SlowBall,
+    /// Launches an extra ball
+    MultiBall,
+    /// Gives back a heart to the paddle the ball is heading towards. It's rarer than the others.
+    Heal,
 }
 
-pub enum Game {
-    Playing(GamePlay),
-    Over(RegularBackground),
+impl PowerUpKind {
+    /// The power ups that turn up as often as each other
+    const COMMON: [PowerUpKind; 4] = [
+        PowerUpKind::EnlargePaddle,
+        PowerUpKind::ShrinkOpponent,
+        PowerUpKind::SlowBall,
+        PowerUpKind::MultiBall,
+    ];
 }
 
-impl Game {
-    pub fn new() -> Self {
-        let ball = Ball::new(vec2(num!(50), num!(50)), vec2(num!(2), num!(0.5)));
-        let paddle_a = Paddle::new(vec2(num!(8), num!(8)), 3); // left paddle
-        let paddle_b = Paddle::new(vec2(num!(240 - 16 - 8), num!(8)), 3); // right paddle
+/// One in this many power ups is a heal, in the games where there are hearts to lose
+const HEAL_RARITY: u32 = 8;
+/// How far power ups turn up from the paddles' ends of the court, on top of the paddle inset,
+/// and from the top and bottom of it
+const POWER_UP_CLEARANCE: (i32, i32) = (40, 24);
 
-        let mut bg = RegularBackground::new(
-            Priority::P3,
-            RegularBackgroundSize::Background32x32,
-            TileFormat::FourBpp,
+/// How long a paddle stays resized by a power up, and by how many segments
+const PADDLE_RESIZE_DURATION: u16 = 60 * 10;
+const PADDLE_RESIZE_SEGMENTS: i32 = 1;
+
+pub struct PowerUp {
+    pos: Vector2D<FixedNum<8>>,
+    kind: PowerUpKind,
+}
+
+impl PowerUp {
+    pub fn new(pos: Vector2D<FixedNum<8>>, kind: PowerUpKind) -> Self {
+        Self { pos, kind }
+    }
+
+    /// Picks a random kind of power up and puts it somewhere in the middle of `court`, well away
+    /// from the paddles `paddle_inset` in from its ends. Heals only turn up if `can_heal`.
+    pub fn random(
+        rng: &mut Rng,
+        can_heal: bool,
+        court: Rect<FixedNum<8>>,
+        paddle_inset: i32,
+    ) -> Self {
+        let (across, down) = POWER_UP_CLEARANCE;
+        let margin = vec2(FixedNum::new(paddle_inset + across), FixedNum::new(down));
+        let min = court.top_left() + margin;
+        let max = court.bottom_right() - margin - vec2(num!(16), num!(16));
+        let x = rng.range(min.x, max.x);
+        let y = rng.range(min.y, max.y);
+        let kind = if can_heal && rng.next_u32().is_multiple_of(HEAL_RARITY) {
+            PowerUpKind::Heal
+        } else {
+            PowerUpKind::COMMON[rng.next_u32() as usize % PowerUpKind::COMMON.len()]
+        };
+
+        Self::new(vec2(x, y), kind)
+    }
+
+    pub fn collision_rect(&self) -> Rect<FixedNum<8>> {
+        let pos = self.pos + vec2(num!(2), num!(2));
+        Rect::new(pos, vec2(num!(12), num!(12)))
+    }
+
+    pub fn show(&self, frame: &mut impl ObjectSink) {
+        frame.show(
+            Object::new(sprites::POWER_UPS.sprite(self.kind as usize))
+                .set_pos(self.pos.round())
+                .set_priority(Priority::P1),
         );
-        bg.fill_with(&background::PLAY_FIELD);
+    }
+}
+
+/// How long each step of the "3, 2, 1, GO" countdown before a point lasts
+const COUNTDOWN_STEP: u16 = 40;
+/// The balls can move again once the countdown reaches GO
+const COUNTDOWN_LENGTH: u16 = COUNTDOWN_STEP * 4;
+
+/// How far past the target score a deuce can go and still be level before it goes to overtime
+const DEUCE_CAP: u16 = 3;
+
+/// How long the title screen waits for a button press before showing the demo
+const DEMO_DELAY: u16 = 60 * 10;
+
+/// The rules of a match and the numbers it's balanced around. The default is the normal game.
+#[derive(Clone, Copy)]
+pub struct GameConfig {
+    pub win_condition: WinCondition,
+    /// Whether extra balls get launched every `multiball_interval` frames
+    pub multiball: bool,
+    pub power_ups: bool,
+    pub obstacles: ObstacleLayout,
+    /// The velocity each ball starts a point with, before any speedup. The ball's top speed goes
+    /// up and down with it.
+    pub ball_velocity: Vector2D<FixedNum<8>>,
+    /// Pulls the balls along every frame so their shots arc, normally nothing
+    pub gravity: Vector2D<FixedNum<8>>,
+    pub ball_radius: FixedNum<8>,
+    /// Whether the balls bounce off the top and bottom of the screen or wrap round
+    pub edge_mode: EdgeMode,
+    /// Whether gusts of wind blow the balls sideways every so often
+    pub wind: bool,
+    /// The top speed of the player's paddle. The CPU's comes from its difficulty.
+    pub paddle_speed: FixedNum<8>,
+    /// How many misses each paddle can take when playing for lives
+    pub starting_health: u16,
+    pub target_score: u16,
+    /// When playing for points, a lead this big ends the game before anyone reaches the target.
+    /// None plays every game out to the target.
+    pub mercy_margin: Option<u16>,
+    /// How long the clock runs for when playing `WinCondition::TimeAttack`, in seconds
+    pub time_limit: u32,
+    /// How many games a paddle needs to win to take the match, so 2 makes it best of 3
+    pub games_to_win: u8,
+    /// The most balls that can be on the field at once in multiball mode
+    pub max_balls: usize,
+    pub multiball_interval: u16,
+    /// How often a new power up appears on the field
+    pub power_up_interval: u16,
+    /// The most power ups that can be waiting on the field at once
+    pub max_power_ups: usize,
+    /// Whether the CPU on the right plays to the score, to keep the game close
+    pub rubber_band: bool,
+    /// The part of the screen the game's played in, normally all of it. Insetting it leaves room
+    /// around the edge for other things, like a scoreboard along the top.
+    pub court: Rect<FixedNum<8>>,
+    /// How far in from their ends of the court the paddles sit, in pixels
+    pub paddle_inset: i32,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            win_condition: WinCondition::Score,
+            multiball: false,
+            power_ups: true,
+            obstacles: ObstacleLayout::None,
+            ball_velocity: vec2(num!(2), num!(0.5)),
+            gravity: vec2(num!(0), num!(0)),
+            ball_radius: BALL_RADIUS,
+            edge_mode: EdgeMode::Bounce,
+            wind: false,
+            paddle_speed: PADDLE_MAX_SPEED,
+            starting_health: 3,
+            target_score: 11,
+            mercy_margin: Some(7),
+            time_limit: 60,
+            games_to_win: 2,
+            max_balls: 3,
+            multiball_interval: 60 * 8,
+            power_up_interval: 60 * 6,
+            max_power_ups: 2,
+            rubber_band: false,
+            court: full_court(),
+            paddle_inset: PADDLE_INSET,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+    /// The top and bottom only have paddles in a four player game, and are walls otherwise
+    Top,
+    Bottom,
+}
+
+impl Side {
+    pub fn opposite(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+            Side::Top => Side::Bottom,
+            Side::Bottom => Side::Top,
+        }
+    }
+
+    /// True for the top and bottom, where the paddles lie flat and move side to side
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Side::Top | Side::Bottom)
+    }
+
+    /// The way out of the court through this side, one pixel long
+    pub fn outward(self) -> Vector2D<FixedNum<8>> {
+        match self {
+            Side::Left => vec2(num!(-1), num!(0)),
+            Side::Right => vec2(num!(1), num!(0)),
+            Side::Top => vec2(num!(0), num!(-1)),
+            Side::Bottom => vec2(num!(0), num!(1)),
+        }
+    }
+}
+
+/// How a game or a whole match ended
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MatchResult {
+    /// The left paddle won
+    P1Win,
+    /// The right paddle, or the bricks, won
+    P2Win,
+    /// Both sides lost their last life at the same moment
+    Draw,
+}
+
+impl MatchResult {
+    /// The result from whether each side has done enough to win, if either has
+    fn from_wins(left_won: bool, right_won: bool) -> Option<Self> {
+        match (left_won, right_won) {
+            (true, true) => Some(MatchResult::Draw),
+            (true, false) => Some(MatchResult::P1Win),
+            (false, true) => Some(MatchResult::P2Win),
+            (false, false) => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WinCondition {
+    /// Each missed ball costs a heart, and the game ends when a paddle runs out
+    Lives,
+    /// Classic Pong, the first paddle to reach the target score wins
+    Score,
+    /// Brick breaker, where the right paddle is replaced by a wall of bricks. Knocking them all
+    /// down wins, and missing the ball costs a heart like in `Lives`.
+    Bricks,
+    /// Whoever has scored the most when the clock runs out wins. If it's a tie, the next point
+    /// wins.
+    TimeAttack,
+}
+
+/// The win conditions in the order the options go through them, which is also how they're saved.
+/// Playing for points comes first, so blank memory in an older save leaves it on that.
+const WIN_CONDITIONS: [WinCondition; 4] = [
+    WinCondition::Score,
+    WinCondition::Lives,
+    WinCondition::Bricks,
+    WinCondition::TimeAttack,
+];
+
+impl WinCondition {
+    pub fn index(self) -> u8 {
+        WIN_CONDITIONS
+            .iter()
+            .position(|&condition| condition == self)
+            .unwrap_or(0) as u8
+    }
 
-        Game::Playing(GamePlay {
-            bg,
-            ball,
+    pub fn from_index(index: u8) -> Option<Self> {
+        WIN_CONDITIONS.get(usize::from(index)).copied()
+    }
+
+    /// The win condition `steps` along the list from this one, wrapping around at either end
+    pub fn cycle(self, steps: i32) -> Self {
+        let index = (i32::from(self.index()) + steps).rem_euclid(WIN_CONDITIONS.len() as i32);
+        WIN_CONDITIONS[index as usize]
+    }
+}
+
+pub struct GamePlay {
+    /// Always contains at least one ball
+    balls: Vec<Ball>,
+    multiball_timer: u16,
+    power_ups: Vec<PowerUp>,
+    power_up_timer: u16,
+    obstacles: Vec<Obstacle>,
+    /// Only there if the wind is turned on
+    wind: Option<Wind>,
+    /// Only used when playing for `WinCondition::Bricks`, and the right paddle sits out
+    bricks: Vec<Brick>,
+    /// While paused nothing moves, but the field is still drawn underneath the pause menu. This
+    /// holds whichever item is picked on the menu.
+    paused: Option<PauseItem>,
+    /// Frames left in the countdown before the point starts
+    countdown: u16,
+    /// Vblanks left on the clock in a time attack game
+    time_left: u32,
+    /// Set once the game's been level for too long, at the end of the clock or a long deuce.
+    /// From then on the next point wins.
+    overtime: bool,
+    shake: ScreenShake,
+    particles: Particles,
+    announcer: Announcer,
+    rng: Rng,
+    /// In the demo the CPU plays both sides, using `demo_ai` for the left paddle
+    demo: bool,
+    demo_ai: AiController,
+    paddle_a: Paddle,
+    paddle_b: Paddle,
+    /// Shared controller practice, where one person plays both paddles from the same pad with the
+    /// right paddle on A and B. Otherwise `ai` plays the right paddle.
+    shared_controller: bool,
+    ai: AiController,
+    /// The connection to the other console in a link game, dropped if it goes quiet
+    link: Option<Link>,
+    /// This console's part in a link game. It's kept after a disconnect, so that whoever was
+    /// playing the right paddle carries on with it against the CPU.
+    link_role: Option<Role>,
+    config: GameConfig,
+    /// Kept up over the whole match, carrying on through each rematch
+    stats: MatchStats,
+    /// Buttons pressed on frames too slow to step on, waiting for the next step
+    presses: PressLatch,
+}
+
+impl GamePlay {
+    pub fn new(
+        shared_controller: bool,
+        difficulty: Difficulty,
+        config: GameConfig,
+        mut rng: Rng,
+    ) -> Self {
+        let mut ball = Ball::new(vec2(num!(50), num!(50)), config.ball_velocity)
+            .with_gravity(config.gravity)
+            .with_edge_mode(config.edge_mode)
+            .with_court(config.court)
+            .with_radius(config.ball_radius);
+        // Either side can get the first serve, so no two matches start quite the same way. The
+        // bricks can't serve though, so against them the player always does.
+        let toward = if config.win_condition == WinCondition::Bricks {
+            Side::Right
+        } else if rng.next_u32() & 1 == 0 {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        ball.serve(&mut rng, toward);
+        let paddle_a = Paddle::new(
+            Side::Left,
+            start_pos_in(Side::Left, config.court, config.paddle_inset),
+            config.paddle_speed,
+            config.starting_health,
+        )
+        .with_court(config.court); // left paddle
+        let paddle_b = Paddle::new(
+            Side::Right,
+            start_pos_in(Side::Right, config.court, config.paddle_inset),
+            difficulty.paddle_speed(),
+            config.starting_health,
+        )
+        .with_court(config.court); // right paddle
+
+        GamePlay {
+            balls: vec![ball],
+            multiball_timer: 0,
+            power_ups: Vec::new(),
+            power_up_timer: 0,
+            obstacles: config.obstacles.obstacles(config.court),
+            wind: config.wind.then(Wind::new),
+            bricks: if config.win_condition == WinCondition::Bricks {
+                Brick::wall(config.court, config.paddle_inset)
+            } else {
+                Vec::new()
+            },
+            paused: None,
+            countdown: COUNTDOWN_LENGTH,
+            time_left: steps_for_seconds(config.time_limit),
+            overtime: false,
+            shake: ScreenShake::default(),
+            particles: Particles::new(Rng::new(rng.next_u32())),
+            announcer: Announcer::default(),
+            rng,
+            demo: false,
+            demo_ai: AiController::new(difficulty),
             paddle_a,
             paddle_b,
-        })
+            shared_controller,
+            ai: AiController::new(difficulty),
+            link: None,
+            link_role: None,
+            config,
+            stats: MatchStats::default(),
+            presses: PressLatch::default(),
+        }
     }
-}
 
-// The main function must take 1 arguments and never returns, and must be marked with
-// the #[agb::entry] macro.
-#[agb::entry]
-fn main(mut gba: agb::Gba) -> ! {
-    let mut controller = agb::input::ButtonController::new();
+    /// Turns this into a game against the console on the other end of `link`. Only the one ball
+    /// gets sent over, so link games don't have power ups, multiball, obstacles or wind.
+    fn set_link(&mut self, link: Link) {
+        self.link_role = Some(link.role());
+        self.link = Some(link);
+        self.config.multiball = false;
+        self.config.power_ups = false;
+        self.config.obstacles = ObstacleLayout::None;
+        self.obstacles.clear();
+        self.config.wind = false;
+        self.wind = None;
+        // The other console needs a paddle to play, so there's no brick breaker either. The
+        // clock isn't sent over either, so time attack is out too.
+        if matches!(
+            self.config.win_condition,
+            WinCondition::Bricks | WinCondition::TimeAttack
+        ) {
+            self.config.win_condition = WinCondition::Score;
+            self.bricks.clear();
+        }
+    }
 
-    let mut mixer = gba.mixer.mixer(Frequency::Hz32768);
-    let mut tracker = Tracker::new(&BGM);
+    fn has_bricks(&self) -> bool {
+        self.config.win_condition == WinCondition::Bricks
+    }
 
-    let mut gfx = gba.graphics.get();
-    VRAM_MANAGER.set_background_palettes(&background::PALETTES);
+    /// True while the other console is running the game, so this one just shows what it sends
+    fn is_link_client(&self) -> bool {
+        self.link.as_ref().map(Link::role) == Some(Role::Client)
+    }
 
-    let mut game = Game::new();
+    /// The left paddle is played by the CPU in the demo, and once a link client has lost the host
+    fn left_is_cpu(&self) -> bool {
+        self.demo || (self.link_role == Some(Role::Client) && self.link.is_none())
+    }
 
-    loop {
-        game = match game {
-            Game::Playing(mut gp) => {
-                controller.update();
+    /// Sets up the next game with the same players and rules as this one
+    pub fn rematch(&mut self) -> Self {
+        let mut game = GamePlay::new(
+            self.shared_controller,
+            self.ai.difficulty(),
+            self.config,
+            Rng::new(self.rng.next_u32()),
+        );
+        game.demo = self.demo;
+        if let Some(link) = self.link.take() {
+            game.set_link(link);
+        }
+        game.link_role = self.link_role;
+        game.stats = self.stats();
+        game
+    }
+
+    /// True until the countdown gets to GO. The paddles can still move so the players can get
+    /// ready, but the balls are frozen.
+    pub fn is_counting_down(&self) -> bool {
+        self.countdown > COUNTDOWN_STEP
+    }
 
-                gp.ball
-                    .update(&mut gp.paddle_a, &mut gp.paddle_b, &mut mixer);
+    pub fn update_serves(&mut self, input: &Input) {
+        if self.is_counting_down() || self.is_link_client() {
+            return;
+        }
 
-                gp.paddle_a.update(&mut controller);
-                gp.paddle_b.update(&mut controller);
+        let left_is_cpu = self.left_is_cpu();
+        for ball in &mut self.balls {
+            ball.update_serve(input, left_is_cpu);
+        }
+    }
 
-                let mut frame = gfx.frame();
+    /// Turns what happened during the step into sounds, screen shake and particles
+    pub fn handle_events(&mut self, events: &mut EventSink, sounds: &mut FrameSounds) {
+        self.announcer.update();
+        let rally = self.rally();
+        for event in events.drain() {
+            if let Some(line) = self.announcer.react(&event, rally) {
+                sounds.voice = Some(line);
+            }
+            match event {
+                GameEvent::PaddleHit { side, pos, speed } => {
+                    sounds.ball_paddle_hit.push(SoundCue::hit(pos.x, speed));
+                    self.particles.emit(pos, side.opposite().outward());
+                    self.stats.hits += 1;
+                }
+                GameEvent::WallHit { pos, normal, speed } => {
+                    sounds.wall_hit.push(SoundCue::at(pos.x));
+                    self.shake.trigger(MAX_WALL_SHAKE * speed);
+                    self.particles.emit(pos, normal);
+                }
+                GameEvent::Bounce { pos, normal } => {
+                    sounds.wall_hit.push(SoundCue::at(pos.x));
+                    self.particles.emit(pos, normal);
+                }
+                GameEvent::BrickHit { pos, normal, speed } => {
+                    sounds.ball_paddle_hit.push(SoundCue::hit(pos.x, speed));
+                    self.particles.emit(pos, normal);
+                }
+                GameEvent::Score { .. } => {
+                    self.shake.trigger(SCORE_SHAKE);
+                    self.stats.points += 1;
+                }
+            }
+        }
+    }
+
+    /// Moves on the purely visual effects, returning how far to shake the play field
+    pub fn update_effects(&mut self) -> Vector2D<i32> {
+        self.particles.update();
+        self.shake.update(&mut self.rng)
+    }
 
-                gp.paddle_a.show(&mut frame);
-                gp.paddle_b.show(&mut frame);
-                gp.ball.show(&mut frame);
+    /// Turns any ball that's been in the same rally for far too long, so an unattended demo can't
+    /// get stuck in a bounce that repeats forever
+    fn nudge_stuck_balls(&mut self) {
+        for ball in &mut self.balls {
+            if ball.watchdog(&mut self.rng) {
+                #[cfg(debug_assertions)]
+                agb::println!("Nudged a ball stuck in a rally of {}", ball.rally());
+            }
+        }
+    }
 
-                gp.bg.show(&mut frame);
+    /// Moves the whole game on by a step, returning how far to shake the play field. Nothing in
+    /// here draws anything, so it can just as well run with nobody watching.
+    pub fn step(
+        &mut self,
+        input: &Input,
+        events: &mut EventSink,
+        sounds: &mut FrameSounds,
+    ) -> Vector2D<i32> {
+        self.update_serves(input);
+        self.update_balls(events);
+        self.nudge_stuck_balls();
+        self.update_overtime();
+        self.handle_events(events, sounds);
+        self.update_power_ups();
+        self.update_paddles(input);
+        let shake = self.update_effects();
+        self.update_stats();
+        shake
+    }
 
-                gp.paddle_a.show_health(&mut frame);
-                gp.paddle_b.show_health(&mut frame);
+    pub fn update_paddles(&mut self, input: &Input) {
+        // A link client plays the right paddle, and the left one is moved by the host
+        let link_client = self.link_role == Some(Role::Client);
+        // Both paddles see where the other was at the start of the step
+        let state_a = Some(self.paddle_a.state());
+        let state_b = (!self.has_bricks()).then(|| self.paddle_b.state());
 
-                tracker.step(&mut mixer);
-                mixer.frame();
+        if self.left_is_cpu() {
+            self.paddle_a
+                .update(&mut self.demo_ai, &self.balls, state_b);
+        } else if !link_client {
+            self.paddle_a
+                .update(&mut HumanController::dpad(input), &self.balls, state_b);
+            self.paddle_a.update_charge(input.is_pressed(Button::R));
+        }
 
-                frame.commit();
+        if self.has_bricks() {
+            // The bricks stand in for the right paddle, so there's nothing to move
+        } else if link_client {
+            self.paddle_b
+                .update(&mut HumanController::dpad(input), &self.balls, state_a);
+        } else if self.shared_controller {
+            self.paddle_b.update(
+                &mut HumanController::face_buttons(input),
+                &self.balls,
+                state_a,
+            );
+        } else if self.link.is_none() {
+            let lead = i32::from(self.paddle_b.score()) - i32::from(self.paddle_a.score());
+            self.ai.set_lead(self.config.rubber_band.then_some(lead));
+            self.paddle_b.update(&mut self.ai, &self.balls, state_a);
+        }
+    }
 
-                if gp.paddle_a.health == 0 || gp.paddle_b.health == 0 {
-                    let mut bg = RegularBackground::new(
-                        Priority::P0,
-                        RegularBackgroundSize::Background32x32,
-                        TileFormat::FourBpp,
-                    );
-                    bg.fill_with(&background::GAME_OVER);
+    /// Swaps this frame's state with the other console in a link game. If it's stopped answering
+    /// the game carries on with the CPU taking over its paddle. This goes once a frame however
+    /// many steps there were, including none while paused, so the other console hears from this
+    /// one as often as it expects to.
+    pub fn update_link(&mut self) {
+        let Some(link) = &mut self.link else {
+            return;
+        };
 
-                    Game::Over(bg)
-                } else {
-                    Game::Playing(gp)
+        match link.role() {
+            Role::Host => {
+                let ball = &self.balls[0];
+                let state = HostState {
+                    ball_pos: ball.pos(),
+                    ball_velocity: ball.velocity(),
+                    paddle_y: self.paddle_a.pos().y,
+                    scores: [self.paddle_a.score(), self.paddle_b.score()],
+                    health: [self.paddle_a.health(), self.paddle_b.health()],
+                    countdown: self.countdown,
+                };
+
+                match link.exchange_host(&state) {
+                    Exchange::Received(ClientState { paddle_y }) => {
+                        self.paddle_b.move_by(paddle_y - self.paddle_b.pos().y);
+                    }
+                    Exchange::Idle | Exchange::Missed => {}
+                    Exchange::Disconnected => self.link = None,
                 }
             }
-            Game::Over(bg) => {
-                controller.update();
+            Role::Client => {
+                let state = ClientState {
+                    paddle_y: self.paddle_b.pos().y,
+                };
 
-                let mut frame = gfx.frame();
-                bg.show(&mut frame);
+                match link.exchange_client(&state) {
+                    Exchange::Received(host) => {
+                        self.balls[0].sync(host.ball_pos, host.ball_velocity);
+                        self.paddle_a.move_by(host.paddle_y - self.paddle_a.pos().y);
+                        self.paddle_a.sync(host.scores[0], host.health[0]);
+                        self.paddle_b.sync(host.scores[1], host.health[1]);
+                        self.countdown = host.countdown;
+                    }
+                    Exchange::Idle | Exchange::Missed => {}
+                    Exchange::Disconnected => self.link = None,
+                }
+            }
+        }
+    }
+
+    pub fn update_balls(&mut self, events: &mut EventSink) {
+        if self.is_link_client() {
+            return;
+        }
+
+        // Move the obstacles first, so the balls bounce off where they are this frame
+        for obstacle in &mut self.obstacles {
+            obstacle.update();
+        }
+
+        self.countdown = self.countdown.saturating_sub(1);
+        let counting_down = self.is_counting_down();
+        let has_bricks = self.has_bricks();
+
+        // The wind holds off during the countdown, so a gust can't be half over by the serve
+        if let Some(wind) = &mut self.wind
+            && !counting_down
+        {
+            wind.update(&mut self.rng);
+        }
+        let wind = self
+            .wind
+            .as_ref()
+            .map_or(vec2(num!(0), num!(0)), Wind::force);
+        let mut opponent = if has_bricks {
+            Opponent::Bricks(&mut self.bricks)
+        } else {
+            Opponent::Paddle(&mut self.paddle_b)
+        };
+
+        if counting_down {
+            for ball in &mut self.balls {
+                ball.follow_server(&self.paddle_a, &opponent);
+            }
+            return;
+        }
+
+        let mut i = 0;
+        while i < self.balls.len() {
+            self.balls[i].set_wind(wind);
+            let conceded =
+                self.balls[i].update(&mut self.paddle_a, &mut opponent, &self.obstacles, events);
+            if let Some(conceded) = conceded {
+                // Extra balls just disappear once they're scored, but the last one gets served
+                // again so there's always one in play
+                if self.balls.len() > 1 {
+                    self.balls.swap_remove(i);
+                    continue;
+                }
+                // The bricks can't serve, so the player serves every point against them
+                let toward = if has_bricks { Side::Right } else { conceded };
+                self.balls[i].serve(&mut self.rng, toward);
+                self.countdown = COUNTDOWN_LENGTH;
+            }
+            i += 1;
+        }
+        collide_balls(&mut self.balls, events);
+
+        if self.config.multiball {
+            self.multiball_timer += 1;
+            if self.multiball_timer >= self.config.multiball_interval
+                && self.balls.len() < self.config.max_balls
+            {
+                self.multiball_timer = 0;
+                self.spawn_ball();
+            }
+        }
+    }
+
+    pub fn update_power_ups(&mut self) {
+        if self.is_link_client() {
+            return;
+        }
+
+        self.paddle_a.update_size();
+        self.paddle_b.update_size();
+
+        if !self.config.power_ups {
+            return;
+        }
+
+        self.power_up_timer += 1;
+        if self.power_up_timer >= self.config.power_up_interval {
+            self.power_up_timer = 0;
+            if self.power_ups.len() < self.config.max_power_ups {
+                // Hearts only matter when missing the ball costs one
+                let can_heal = matches!(
+                    self.config.win_condition,
+                    WinCondition::Lives | WinCondition::Bricks
+                );
+                self.power_ups.push(PowerUp::random(
+                    &mut self.rng,
+                    can_heal,
+                    self.config.court,
+                    self.config.paddle_inset,
+                ));
+            }
+        }
+
+        let mut i = 0;
+        while i < self.power_ups.len() {
+            let rect = self.power_ups[i].collision_rect();
+            let collected_by = self
+                .balls
+                .iter()
+                .find(|ball| ball.is_in_play() && ball.collision_mask().touches(rect))
+                .map(|ball| {
+                    let toward = if ball.velocity().x < num!(0) {
+                        Side::Left
+                    } else {
+                        Side::Right
+                    };
+                    (ball.last_hit(), toward)
+                });
+
+            match collected_by {
+                Some((side, toward)) => {
+                    let power_up = self.power_ups.swap_remove(i);
+                    self.apply_power_up(power_up.kind, side, toward);
+                }
+                None => i += 1,
+            }
+        }
+    }
+
+    /// Applies the effect of a collected power up. `side` is the paddle that collected it, if the
+    /// ball has been hit this point, and the paddle effects are wasted if nobody has. `toward` is
+    /// the side the ball was heading for when it picked the power up up.
+    fn apply_power_up(&mut self, kind: PowerUpKind, side: Option<Side>, toward: Side) {
+        let (side, change) = match (kind, side) {
+            (PowerUpKind::EnlargePaddle, Some(side)) => (side, PADDLE_RESIZE_SEGMENTS),
+            (PowerUpKind::ShrinkOpponent, Some(side)) => (side.opposite(), -PADDLE_RESIZE_SEGMENTS),
+            (PowerUpKind::EnlargePaddle | PowerUpKind::ShrinkOpponent, None) => return,
+            (PowerUpKind::SlowBall, _) => {
+                for ball in &mut self.balls {
+                    ball.slow_down();
+                }
+                return;
+            }
+            (PowerUpKind::MultiBall, _) => {
+                if self.balls.len() < self.config.max_balls {
+                    self.spawn_ball();
+                }
+                return;
+            }
+            (PowerUpKind::Heal, _) => {
+                if let Some(paddle) = self.paddle_mut(toward) {
+                    paddle.heal();
+                }
+                return;
+            }
+        };
+
+        if let Some(paddle) = self.paddle_mut(side) {
+            paddle.resize_for(change, PADDLE_RESIZE_DURATION);
+        }
+    }
+
+    /// The paddle on `side`, if there is one. There are only paddles at either end of the court.
+    fn paddle_mut(&mut self, side: Side) -> Option<&mut Paddle> {
+        match side {
+            Side::Left => Some(&mut self.paddle_a),
+            Side::Right => Some(&mut self.paddle_b),
+            Side::Top | Side::Bottom => None,
+        }
+    }
+
+    pub fn show_power_ups(&self, frame: &mut impl ObjectSink) {
+        for power_up in &self.power_ups {
+            power_up.show(frame);
+        }
+    }
+
+    pub fn show_obstacles(&self, frame: &mut impl ObjectSink) {
+        for obstacle in &self.obstacles {
+            obstacle.show(frame);
+        }
+    }
+
+    pub fn show_wind(&self, frame: &mut impl ObjectSink) {
+        if let Some(wind) = &self.wind {
+            wind.show(frame);
+        }
+    }
+
+    /// Draws whatever's playing on the right, the paddle or the bricks in its place
+    pub fn show_opponent(&self, frame: &mut impl ObjectSink) {
+        if !self.has_bricks() {
+            self.paddle_b.show(frame);
+        }
+        for brick in &self.bricks {
+            brick.show(frame);
+        }
+    }
+
+    /// Launches an extra ball from the centre of the field, alternating which side it heads for
+    pub fn spawn_ball(&mut self) {
+        let base = self.config.ball_velocity;
+        let velocity = if self.balls.len().is_multiple_of(2) {
+            base
+        } else {
+            vec2(-base.x, base.y)
+        };
+
+        let radius = self.config.ball_radius;
+        let mut pos = self.config.court.centre() - vec2(radius, radius);
+        // The hazard slides up and down through the centre, so a ball launched while it's there
+        // starts just clear of it instead, on the side it's heading for. The hazard only moves up
+        // and down, so it can't catch the ball up from there.
+        for obstacle in &self.obstacles {
+            let rect = obstacle.collision_rect();
+            if Circle::new(pos, radius).touches(rect) {
+                pos.x = if velocity.x < num!(0) {
+                    rect.top_left().x - radius * 2 - num!(1)
+                } else {
+                    rect.bottom_right().x + num!(1)
+                };
+            }
+        }
+
+        let mut ball = Ball::new(pos, velocity)
+            .with_gravity(self.config.gravity)
+            .with_edge_mode(self.config.edge_mode)
+            .with_court(self.config.court)
+            .with_radius(radius);
+        ball.launch();
+        self.balls.push(ball);
+    }
+
+    /// Keeps track of the longest rally so far
+    pub fn update_stats(&mut self) {
+        self.stats.longest_rally = self.stats.longest_rally.max(self.rally());
+    }
+
+    /// Counts the `vblanks` since the last frame towards how long the match has gone on, and runs
+    /// down the time attack clock by them. This goes by the vblanks rather than the steps taken,
+    /// so that both keep to real time however fast the game's running or however long a frame
+    /// took to draw.
+    pub fn count_frames(&mut self, vblanks: u32) {
+        self.stats.frames += vblanks;
+        self.update_clock(vblanks);
+    }
+
+    /// How the match has gone so far, with the points in this game added on to the earlier ones
+    pub fn stats(&self) -> MatchStats {
+        let [a, b] = self.stats.score;
+        MatchStats {
+            score: [a + self.paddle_a.score(), b + self.paddle_b.score()],
+            ..self.stats
+        }
+    }
+
+    /// The longest rally of any of the balls in play
+    pub fn rally(&self) -> u32 {
+        self.balls
+            .iter()
+            .map(|ball| ball.rally())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Shows numbers for tuning the physics in the bottom left corner. From the top down they're
+    /// the first ball's speed, its x and y velocity, all in hundredths of a pixel per frame, then
+    /// the rally and `frames` since power on.
+    ///
+    /// This should be shown after the rest of the game, and before only the decorations in the
+    /// [`SpriteBudget`]. Anything past the hardware's object limit is dropped, so if there are too
+    /// many objects on screen it's the overlay that goes rather than the game.
+    pub fn show_debug_overlay(&self, frames: u32, frame: &mut impl ObjectSink) {
+        let hundredths = |value: FixedNum<8>| (value * 100).floor();
+        let ball = &self.balls[0];
+        let velocity = ball.velocity();
+        let row = |i: i32| vec2(48, agb::display::HEIGHT - 48 + i * 8);
+
+        show_number(hundredths(ball.speed()) as u32, row(0), frame);
+        show_signed_number(hundredths(velocity.x), row(1), frame);
+        show_signed_number(hundredths(velocity.y), row(2), frame);
+        show_number(self.rally(), row(3), frame);
+        show_number(frames, row(4), frame);
+    }
+
+    /// Runs down the time attack clock by `vblanks`. It stops during the countdown before each
+    /// point, so the time only goes while the ball's in play.
+    fn update_clock(&mut self, vblanks: u32) {
+        if self.config.win_condition == WinCondition::TimeAttack && !self.is_counting_down() {
+            self.time_left = self.time_left.saturating_sub(vblanks);
+        }
+    }
+
+    /// Shows the time attack clock as minutes and seconds, under the rally
+    pub fn show_clock(&self, frame: &mut impl ObjectSink) {
+        let seconds = seconds_for_steps(self.time_left);
+        let (x, y) = (WIDTH / 2, 14);
+
+        // The minutes go to the left of the colon and the seconds always take two digits
+        show_number(seconds / 60, vec2(x - 4, y), frame);
+        frame.show(Object::new(sprites::COLON.sprite(0)).set_pos(vec2(x - 4, y)));
+        show_number((seconds % 60) / 10, vec2(x + 12, y), frame);
+        show_number(seconds % 10, vec2(x + 20, y), frame);
+    }
+
+    /// Goes to overtime if the scores are level when the clock runs out, or are still level a
+    /// while into a deuce. Overtime starts with a fresh serve, so nobody is caught out by the
+    /// point that's going to win it.
+    pub fn update_overtime(&mut self) {
+        let (a, b) = (self.paddle_a.score(), self.paddle_b.score());
+        let level_too_long = match self.config.win_condition {
+            WinCondition::Score => a >= self.config.target_score + DEUCE_CAP,
+            WinCondition::TimeAttack => self.time_left == 0,
+            WinCondition::Lives | WinCondition::Bricks => false,
+        };
+        if self.overtime || a != b || !level_too_long {
+            return;
+        }
+
+        self.overtime = true;
+        // The host sends over the countdown, and after a deuce a point has only just been scored
+        // so it's already running
+        if self.is_link_client() || self.is_counting_down() {
+            return;
+        }
+        self.balls.truncate(1);
+        let toward = if self.balls[0].velocity().x < num!(0) {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        self.balls[0].serve(&mut self.rng, toward);
+        self.countdown = COUNTDOWN_LENGTH;
+    }
+
+    /// Marks where each ball coming towards the player will meet their paddle, using the same
+    /// prediction as the CPU. Balls heading the other way or waiting to be served aren't marked.
+    pub fn show_landing(&self, frame: &mut impl ObjectSink) {
+        let paddle_x = self.paddle_a.collision_rect().bottom_right().x;
+
+        for ball in &self.balls {
+            if !ball.is_in_play() || ball.velocity().x >= num!(0) {
+                continue;
+            }
+
+            // The marker sits in the gap behind the paddle, pointing at where the ball's centre
+            // will be
+            let y = ball.predict_y_at_x(paddle_x) + ball.radius();
+            frame.show(
+                Object::new(sprites::LANDING.sprite(0))
+                    .set_pos(vec2(0, y.round() - 4))
+                    .set_priority(Priority::P1),
+            );
+        }
+    }
+
+    pub fn show_rally(&self, frame: &mut impl ObjectSink) {
+        show_number(self.rally(), vec2(WIDTH / 2 + 8, 4), frame);
+    }
+
+    /// How the game ended, or None while it's still going
+    pub fn result(&self) -> Option<MatchResult> {
+        let (a, b) = (self.paddle_a.score(), self.paddle_b.score());
+        let (left_won, right_won) = match self.config.win_condition {
+            _ if self.overtime => (a > b, b > a),
+            WinCondition::Lives => (self.paddle_b.health() == 0, self.paddle_a.health() == 0),
+            WinCondition::Bricks => (self.bricks.is_empty(), self.paddle_a.health() == 0),
+            WinCondition::Score => {
+                // You need to reach the target score with a two point lead, so from deuce the game
+                // carries on until somebody pulls ahead. A big enough lead wins it early.
+                let skunk = self.is_skunk();
+                (
+                    (a >= self.config.target_score && a >= b + 2) || (skunk && a > b),
+                    (b >= self.config.target_score && b >= a + 2) || (skunk && b > a),
+                )
+            }
+            WinCondition::TimeAttack => {
+                let time_up = self.time_left == 0;
+                (time_up && a > b, time_up && b > a)
+            }
+        };
+
+        // With several balls in play both paddles can lose their last life on the same step,
+        // which is a draw
+        MatchResult::from_wins(left_won, right_won)
+    }
+
+    /// True if one side is far enough ahead that the mercy rule ends the game
+    pub fn is_skunk(&self) -> bool {
+        let (a, b) = (self.paddle_a.score(), self.paddle_b.score());
+        self.config.win_condition == WinCondition::Score
+            && self
+                .config
+                .mercy_margin
+                .is_some_and(|margin| a.abs_diff(b) >= margin)
+    }
+
+    pub fn is_deuce(&self) -> bool {
+        self.config.win_condition == WinCondition::Score
+            && !self.overtime
+            && self.paddle_a.score() >= self.config.target_score - 1
+            && self.paddle_b.score() >= self.config.target_score - 1
+    }
+
+    pub fn show_countdown(&self, frame: &mut impl ObjectSink) {
+        show_countdown(self.countdown, frame);
+    }
+
+    pub fn show_paused(&self, frame: &mut impl ObjectSink) {
+        if let Some(selected) = self.paused {
+            show_pause_menu(selected, frame);
+        }
+    }
+
+    pub fn show_overtime(&self, frame: &mut impl ObjectSink) {
+        let mut top_left = vec2(WIDTH / 2 - 20, 16);
+
+        for i in 0..5 {
+            frame.show(Object::new(sprites::OVERTIME.sprite(i)).set_pos(top_left));
+            top_left.x += 8;
+        }
+    }
+
+    pub fn show_deuce(&self, frame: &mut impl ObjectSink) {
+        let mut top_left = vec2(WIDTH / 2 - 12, 16);
+
+        for i in 0..3 {
+            frame.show(Object::new(sprites::DEUCE.sprite(i)).set_pos(top_left));
+            top_left.x += 8;
+        }
+    }
+}
+
+/// How a match went, for the summary on the game over screen
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    /// The most hits in a row without anybody scoring
+    longest_rally: u32,
+    /// Every time the ball came off either paddle
+    hits: u32,
+    /// Every point played, whoever won it
+    points: u32,
+    /// How many vblanks the match was played for, over all its games, whatever speed it was at
+    frames: u32,
+    /// The points won over all the games, left then right. While a game's being played this
+    /// only counts the ones before it, see [`GamePlay::stats`].
+    score: [u16; 2],
+}
+
+pub struct MatchState {
+    game: GamePlay,
+    games_won: [u8; 2],
+    games_to_win: u8,
+    /// Whether the match has been saved yet. Until it has, the saved match is still the one from
+    /// before, which starting a new match and quitting it straight away mustn't lose.
+    saved: bool,
+}
+
+impl MatchState {
+    pub fn new(game: GamePlay, games_to_win: u8) -> Self {
+        Self {
+            game,
+            games_won: [0, 0],
+            games_to_win,
+            saved: false,
+        }
+    }
+
+    /// Records the result of a game, returning the result of the whole match if that settles
+    /// it. Otherwise the next game is set up ready to play. A drawn game counts for both sides,
+    /// so the match is drawn if that takes them both to the games needed to win.
+    pub fn finish_game(&mut self, result: MatchResult) -> Option<MatchResult> {
+        let [left, right] = &mut self.games_won;
+        match result {
+            MatchResult::P1Win => *left += 1,
+            MatchResult::P2Win => *right += 1,
+            MatchResult::Draw => {
+                *left += 1;
+                *right += 1;
+            }
+        }
+
+        let match_result =
+            MatchResult::from_wins(*left >= self.games_to_win, *right >= self.games_to_win);
+        if match_result.is_none() {
+            self.game = self.game.rematch();
+        }
+        match_result
+    }
+
+    pub fn show_games_won(&self, frame: &mut impl ObjectSink) {
+        let y = agb::display::HEIGHT - 12;
+        show_number(self.games_won[0].into(), vec2(WIDTH / 2 - 4, y), frame);
+        show_number(self.games_won[1].into(), vec2(WIDTH / 2 + 12, y), frame);
+    }
+}
+
+pub enum Game {
+    /// `idle_frames` counts how long it's been since a button was pressed, and once it's been
+    /// long enough the demo starts
+    Title {
+        idle_frames: u16,
+    },
+    /// `scroll` is how many options are scrolled off the top, as they don't all fit on the screen
+    Options {
+        selected: OptionsItem,
+        scroll: i32,
+    },
+    /// Boxed because the match is much bigger than the other states
+    Playing(Box<MatchState>),
+    FourPlayer(Box<FourPlayerGame>),
+    /// `skunk` is set if the mercy rule ended the last game. There aren't any `stats` after a
+    /// four player game.
+    Over {
+        result: MatchResult,
+        skunk: bool,
+        stats: Option<MatchStats>,
+        /// Kept going from a link game, so the next one can be against the same console
+        link: Option<Link>,
+    },
+}
+
+impl Game {
+    pub fn new(
+        shared_controller: bool,
+        difficulty: Difficulty,
+        config: GameConfig,
+        rng: Rng,
+        link: Option<Link>,
+    ) -> Self {
+        let mut game = GamePlay::new(shared_controller, difficulty, config, rng);
+        if let Some(link) = link {
+            game.set_link(link);
+        }
+        Game::Playing(Box::new(MatchState::new(game, config.games_to_win)))
+    }
+
+    /// A four player game, which isn't recorded or played over the link cable
+    pub fn four_player(difficulty: Difficulty, config: GameConfig, rng: Rng) -> Self {
+        Game::FourPlayer(Box::new(FourPlayerGame::new(difficulty, config, rng)))
+    }
+
+    /// A match with the CPU playing itself, which carries on until somebody presses a button.
+    /// It always shows off the power ups, but with just the one ball.
+    pub fn demo(difficulty: Difficulty, config: GameConfig, rng: Rng) -> Self {
+        let config = GameConfig {
+            multiball: false,
+            power_ups: true,
+            ..config
+        };
+        let mut game = GamePlay::new(false, difficulty, config, rng);
+        game.demo = true;
+        Game::Playing(Box::new(MatchState::new(game, config.games_to_win)))
+    }
+
+    pub fn options() -> Self {
+        Game::Options {
+            selected: OptionsItem::Volume,
+            scroll: 0,
+        }
+    }
+
+    pub fn title() -> Self {
+        Game::Title { idle_frames: 0 }
+    }
+}
+
+/// Every background the game uses, made once at startup and shared between the states rather
+/// than set up again on every change of screen
+struct Backgrounds {
+    play_field: RegularBackground,
+    title: RegularBackground,
+    options: RegularBackground,
+    game_over: RegularBackground,
+}
+
+impl Backgrounds {
+    fn new() -> Self {
+        let mut play_field = RegularBackground::new(
+            Priority::P3,
+            RegularBackgroundSize::Background32x32,
+            TileFormat::FourBpp,
+        );
+        // The dashed net down the middle is part of the play field image, so it sits behind the
+        // balls and paddles without using up any objects
+        play_field.fill_with(&background::PLAY_FIELD);
+
+        Self {
+            play_field,
+            title: full_screen_background(&background::TITLE),
+            options: full_screen_background(&background::OPTIONS),
+            game_over: full_screen_background(&background::GAME_OVER),
+        }
+    }
+}
+
+/// Makes a background that covers the whole screen and everything else on it, for the screens
+/// between games
+fn full_screen_background(tiles: &TileData) -> RegularBackground {
+    let mut bg = RegularBackground::new(
+        Priority::P0,
+        RegularBackgroundSize::Background32x32,
+        TileFormat::FourBpp,
+    );
+    bg.fill_with(tiles);
+    bg
+}
+
+/// The buttons the menus respond to, each of which only counts on the frame it goes down so that
+/// holding it doesn't repeat the action every frame
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    /// START, to begin a game or pause one
+    Start,
+    /// SELECT
+    Select,
+    /// A
+    Confirm,
+    /// B
+    Back,
+}
+
+/// The menu action pressed this frame, if any. If several go down at once the first in
+/// `MenuAction` wins.
+fn menu_select(input: &Input) -> Option<MenuAction> {
+    [
+        (Button::START, MenuAction::Start),
+        (Button::SELECT, MenuAction::Select),
+        (Button::A, MenuAction::Confirm),
+        (Button::B, MenuAction::Back),
+    ]
+    .into_iter()
+    .find(|&(button, _)| input.is_just_pressed(button))
+    .map(|(_, action)| action)
+}
+
+/// What can be picked on the pause menu
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PauseItem {
+    Resume,
+    /// Starts the match over with the same settings
+    Restart,
+    /// Gives up on the match and goes back to the title screen
+    Quit,
+}
+
+/// The pause menu, top to bottom, under the PAUSED banner
+const PAUSE_ITEMS: [PauseItem; 3] = [PauseItem::Resume, PauseItem::Restart, PauseItem::Quit];
+
+/// Where the PAUSED banner goes, with the pause menu underneath it
+const PAUSED_Y: i32 = agb::display::HEIGHT / 2 - 20;
+const PAUSE_SPACING: i32 = 10;
+
+impl PauseItem {
+    fn index(self) -> i32 {
+        PAUSE_ITEMS
+            .iter()
+            .position(|&item| item == self)
+            .unwrap_or(0) as i32
+    }
+
+    fn y(self) -> i32 {
+        PAUSED_Y + (self.index() + 1) * PAUSE_SPACING + 4
+    }
+
+    /// The item `steps` further down the menu, wrapping around at either end
+    fn cycle(self, steps: i32) -> Self {
+        PAUSE_ITEMS[(self.index() + steps).rem_euclid(PAUSE_ITEMS.len() as i32) as usize]
+    }
+
+    fn label(self) -> &'static Tag {
+        match self {
+            PauseItem::Resume => &sprites::RESUME,
+            PauseItem::Restart => &sprites::RESTART,
+            PauseItem::Quit => &sprites::QUIT,
+        }
+    }
+}
+
+/// The PAUSED banner with the pause menu under it, drawn over the top of everything else
+fn show_pause_menu(selected: PauseItem, frame: &mut impl ObjectSink) {
+    let mut top_left = vec2(WIDTH / 2 - 16, PAUSED_Y);
+
+    for i in 0..4 {
+        frame.show(
+            Object::new(sprites::PAUSED.sprite(i))
+                .set_pos(top_left)
+                .set_priority(Priority::P0),
+        );
+        top_left.x += 8;
+    }
+
+    frame.show(
+        Object::new(sprites::PAUSE_CURSOR.sprite(0))
+            .set_pos(vec2(WIDTH / 2 - 28, selected.y()))
+            .set_priority(Priority::P0),
+    );
+    for item in PAUSE_ITEMS {
+        let mut top_left = vec2(WIDTH / 2 - 16, item.y());
+        for sprite in item.label().sprites() {
+            frame.show(
+                Object::new(sprite)
+                    .set_pos(top_left)
+                    .set_priority(Priority::P0),
+            );
+            top_left.x += 8;
+        }
+    }
+}
+
+/// Opens or closes the pause menu when START goes down, and moves around it while it's open,
+/// returning the item picked if there is one. Only the frame START goes down counts, as holding
+/// it would flicker in and out of the pause. Picking Resume closes the menu again.
+fn update_pause_menu(
+    paused: &mut Option<PauseItem>,
+    action: Option<MenuAction>,
+    input: &Input,
+) -> Option<PauseItem> {
+    if action == Some(MenuAction::Start) {
+        *paused = match paused {
+            Some(_) => None,
+            None => Some(PauseItem::Resume),
+        };
+    }
+
+    let selected = paused.as_mut()?;
+    let down = i32::from(input.is_just_pressed(Button::DOWN));
+    let up = i32::from(input.is_just_pressed(Button::UP));
+    *selected = selected.cycle(down - up);
+
+    let picked = (action == Some(MenuAction::Confirm)).then_some(*selected);
+    if picked == Some(PauseItem::Resume) {
+        *paused = None;
+    }
+    picked
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OptionsItem {
+    Difficulty,
+    Mode,
+    Lives,
+    Volume,
+    Mute,
+    Theme,
+    Speed,
+    Turbo,
+    SlowPractice,
+    LargeBall,
+    Assist,
+    Edges,
+    MercyRule,
+    Players,
+    CatchUp,
+    Multiball,
+    Obstacles,
+    Gravity,
+    Wind,
+}
+
+/// The options screen, top to bottom. A new option only needs adding here and to the matches in
+/// [`OptionsItem`], and the screen lays itself out around it.
+const OPTIONS_ITEMS: [OptionsItem; 19] = [
+    OptionsItem::Difficulty,
+    OptionsItem::Mode,
+    OptionsItem::Lives,
+    OptionsItem::Volume,
+    OptionsItem::Mute,
+    OptionsItem::Theme,
+    OptionsItem::Speed,
+    OptionsItem::Turbo,
+    OptionsItem::SlowPractice,
+    OptionsItem::LargeBall,
+    OptionsItem::Assist,
+    OptionsItem::Edges,
+    OptionsItem::MercyRule,
+    OptionsItem::Players,
+    OptionsItem::CatchUp,
+    OptionsItem::Multiball,
+    OptionsItem::Obstacles,
+    OptionsItem::Gravity,
+    OptionsItem::Wind,
+];
+
+/// Where the first option goes, under the title, and how far down each one after it is
+const OPTIONS_TOP: i32 = 48;
+const OPTIONS_SPACING: i32 = 9;
+/// How many options fit on the screen at once. The list scrolls to keep the selected one on it.
+const OPTIONS_SHOWN: i32 = 12;
+
+/// What an option shows next to its label
+enum OptionValue {
+    Number(u32),
+    OnOff(bool),
+    Text(&'static Tag),
+}
+
+impl OptionsItem {
+    fn index(self) -> i32 {
+        OPTIONS_ITEMS
+            .iter()
+            .position(|&item| item == self)
+            .unwrap_or(0) as i32
+    }
+
+    /// Where the item goes on the options screen, with the first `scroll` items scrolled off
+    fn y(self, scroll: i32) -> i32 {
+        OPTIONS_TOP + (self.index() - scroll) * OPTIONS_SPACING
+    }
+
+    /// How far to scroll the list from `scroll` to bring this item on to the screen, which is
+    /// only as far as it takes
+    fn scroll_to(self, scroll: i32) -> i32 {
+        scroll.clamp(self.index() - OPTIONS_SHOWN + 1, self.index())
+    }
+
+    /// The item `steps` further down the list, wrapping around at either end
+    fn cycle(self, steps: i32) -> Self {
+        OPTIONS_ITEMS[(self.index() + steps).rem_euclid(OPTIONS_ITEMS.len() as i32) as usize]
+    }
+
+    fn label(self) -> &'static Tag {
+        match self {
+            OptionsItem::Difficulty => &sprites::DIFFICULTY_LABEL,
+            OptionsItem::Mode => &sprites::MODE_LABEL,
+            OptionsItem::Lives => &sprites::LIVES_LABEL,
+            OptionsItem::Volume => &sprites::VOLUME_LABEL,
+            OptionsItem::Mute => &sprites::MUTE_LABEL,
+            OptionsItem::Theme => &sprites::THEME_LABEL,
+            OptionsItem::Speed => &sprites::SPEED_LABEL,
+            OptionsItem::Turbo => &sprites::TURBO_LABEL,
+            OptionsItem::SlowPractice => &sprites::SLOW_LABEL,
+            OptionsItem::LargeBall => &sprites::BIG_BALL_LABEL,
+            OptionsItem::Assist => &sprites::ASSIST_LABEL,
+            OptionsItem::Edges => &sprites::EDGES_LABEL,
+            OptionsItem::MercyRule => &sprites::SKUNK_RULE_LABEL,
+            OptionsItem::Players => &sprites::PLAYERS_LABEL,
+            OptionsItem::CatchUp => &sprites::CATCH_UP_LABEL,
+            OptionsItem::Multiball => &sprites::MULTIBALL_LABEL,
+            OptionsItem::Obstacles => &sprites::OBSTACLES_LABEL,
+            OptionsItem::Gravity => &sprites::GRAVITY_LABEL,
+            OptionsItem::Wind => &sprites::WIND_LABEL,
+        }
+    }
+
+    fn value(self, settings: &Settings) -> OptionValue {
+        match self {
+            OptionsItem::Difficulty => OptionValue::Text(match settings.difficulty {
+                Difficulty::Easy => &sprites::EASY,
+                Difficulty::Medium => &sprites::MEDIUM,
+                Difficulty::Hard => &sprites::HARD,
+            }),
+            OptionsItem::Mode => OptionValue::Text(match settings.win_condition {
+                WinCondition::Score => &sprites::SCORE_MODE,
+                WinCondition::Lives => &sprites::LIVES_MODE,
+                WinCondition::Bricks => &sprites::BRICKS_MODE,
+                WinCondition::TimeAttack => &sprites::TIME_MODE,
+            }),
+            OptionsItem::Lives => OptionValue::Number(settings.lives.into()),
+            OptionsItem::Volume => {
+                OptionValue::Number((settings.volume / VOLUME_STEP).floor() as u32)
+            }
+            OptionsItem::Mute => OptionValue::OnOff(settings.muted),
+            OptionsItem::Theme => OptionValue::Text(match settings.theme {
+                Theme::Classic => &sprites::CLASSIC,
+                Theme::Green => &sprites::GREEN,
+                Theme::Amber => &sprites::AMBER,
+                Theme::Neon => &sprites::NEON,
+            }),
+            OptionsItem::Speed => OptionValue::Number(settings.game_speed.percent()),
+            OptionsItem::Turbo => OptionValue::OnOff(settings.turbo),
+            OptionsItem::SlowPractice => OptionValue::OnOff(settings.slow_practice),
+            OptionsItem::LargeBall => OptionValue::OnOff(settings.large_ball),
+            OptionsItem::Assist => OptionValue::OnOff(settings.assist),
+            OptionsItem::Edges => OptionValue::Text(match settings.edge_mode {
+                EdgeMode::Bounce => &sprites::BOUNCE,
+                EdgeMode::Wrap => &sprites::WRAP,
+            }),
+            OptionsItem::MercyRule => OptionValue::OnOff(settings.mercy_rule),
+            OptionsItem::Players => OptionValue::Number(if settings.four_player { 4 } else { 2 }),
+            OptionsItem::CatchUp => OptionValue::OnOff(settings.rubber_band),
+            OptionsItem::Multiball => OptionValue::OnOff(settings.multiball),
+            OptionsItem::Obstacles => OptionValue::Text(match settings.obstacles {
+                ObstacleLayout::None => &sprites::NO_OBSTACLES,
+                ObstacleLayout::Pillars => &sprites::PILLARS,
+                ObstacleLayout::Staggered => &sprites::STAGGERED,
+                ObstacleLayout::Hazard => &sprites::HAZARD,
+            }),
+            OptionsItem::Gravity => OptionValue::OnOff(settings.gravity),
+            OptionsItem::Wind => OptionValue::OnOff(settings.wind),
+        }
+    }
+
+    /// Changes the item's setting for `steps` presses of right, or left if negative. `confirm`
+    /// flips the switches and moves the lists on by one, but leaves the numbers alone. Returns
+    /// whether anything changed.
+    fn adjust(self, settings: &mut Settings, steps: i32, confirm: bool) -> bool {
+        let toggle = steps != 0 || confirm;
+        let steps = if confirm { 1 } else { steps };
+        match self {
+            OptionsItem::Difficulty => {
+                let difficulty = settings.difficulty;
+                settings.difficulty = difficulty.change(steps);
+                settings.difficulty != difficulty
+            }
+            OptionsItem::Mode => {
+                settings.win_condition = settings.win_condition.cycle(steps);
+                steps != 0
+            }
+            OptionsItem::Lives => {
+                let lives = settings.lives;
+                settings.change_lives(if confirm { 0 } else { steps });
+                settings.lives != lives
+            }
+            OptionsItem::Volume => {
+                let volume = settings.volume;
+                settings.change_volume(if confirm { 0 } else { steps });
+                settings.volume != volume
+            }
+            OptionsItem::Mute => {
+                settings.muted ^= toggle;
+                toggle
+            }
+            OptionsItem::Theme => {
+                settings.theme = settings.theme.cycle(steps);
+                steps != 0
+            }
+            OptionsItem::Speed => {
+                let speed = settings.game_speed;
+                settings.game_speed = speed.change(if confirm { 0 } else { steps });
+                settings.game_speed != speed
+            }
+            OptionsItem::Turbo => {
+                settings.turbo ^= toggle;
+                settings.slow_practice &= !settings.turbo;
+                toggle
+            }
+            OptionsItem::SlowPractice => {
+                settings.slow_practice ^= toggle;
+                settings.turbo &= !settings.slow_practice;
+                toggle
+            }
+            OptionsItem::LargeBall => {
+                settings.large_ball ^= toggle;
+                toggle
+            }
+            OptionsItem::Assist => {
+                settings.assist ^= toggle;
+                toggle
+            }
+            OptionsItem::Edges => {
+                if toggle {
+                    settings.edge_mode = settings.edge_mode.toggled();
+                }
+                toggle
+            }
+            OptionsItem::MercyRule => {
+                settings.mercy_rule ^= toggle;
+                toggle
+            }
+            OptionsItem::Players => {
+                settings.four_player ^= toggle;
+                toggle
+            }
+            OptionsItem::CatchUp => {
+                settings.rubber_band ^= toggle;
+                toggle
+            }
+            OptionsItem::Multiball => {
+                settings.multiball ^= toggle;
+                toggle
+            }
+            OptionsItem::Obstacles => {
+                settings.obstacles = settings.obstacles.cycle(steps);
+                steps != 0
+            }
+            OptionsItem::Gravity => {
+                settings.gravity ^= toggle;
+                toggle
+            }
+            OptionsItem::Wind => {
+                settings.wind ^= toggle;
+                toggle
+            }
+        }
+    }
+}
+
+/// Draws the label of every option that's scrolled on to the screen, with its current setting
+/// next to it
+fn show_options(
+    settings: &Settings,
+    selected: OptionsItem,
+    scroll: i32,
+    frame: &mut GraphicsFrame,
+) {
+    frame.show(Object::new(sprites::CURSOR.sprite(0)).set_pos(vec2(60, selected.y(scroll))));
+
+    let shown = OPTIONS_ITEMS.into_iter().skip(scroll as usize);
+    for item in shown.take(OPTIONS_SHOWN as usize) {
+        let y = item.y(scroll);
+        show_text(item.label(), vec2(72, y), frame);
+        match item.value(settings) {
+            OptionValue::Number(value) => {
+                show_number_with(&sprites::LIGHT_DIGITS, value, vec2(168, y), frame);
+            }
+            OptionValue::OnOff(on) => {
+                let text = if on { &sprites::ON } else { &sprites::OFF };
+                show_text(text, vec2(152, y), frame);
+            }
+            OptionValue::Text(text) => show_text(text, vec2(152, y), frame),
+        }
+    }
+}
+
+/// Draws each frame of `text` in a row, starting from `top_left`
+fn show_text(text: &Tag, mut top_left: Vector2D<i32>, frame: &mut impl ObjectSink) {
+    for sprite in text.sprites() {
+        frame.show(Object::new(sprite).set_pos(top_left));
+        top_left.x += 8;
+    }
+}
+
+/// Announces who took the match, or that it was a draw, in white for the game over screen
+fn show_result(result: MatchResult, frame: &mut GraphicsFrame) {
+    let text = match result {
+        MatchResult::P1Win => &sprites::PLAYER_WINS,
+        MatchResult::P2Win => &sprites::CPU_WINS,
+        MatchResult::Draw => &sprites::DRAW,
+    };
+
+    let width = 8 * text.sprites().len() as i32;
+    let mut top_left = vec2(WIDTH / 2 - width / 2, 52);
+
+    for sprite in text.sprites() {
+        frame.show(Object::new(sprite).set_pos(top_left));
+        top_left.x += 8;
+    }
+}
+
+/// A bit of extra gloating under the game over when the mercy rule ended the last game
+fn show_skunk(frame: &mut GraphicsFrame) {
+    let width = 8 * sprites::SKUNK.sprites().len() as i32;
+    show_text(&sprites::SKUNK, vec2(WIDTH / 2 - width / 2, 92), frame);
+}
+
+/// The match summary under the best rally, with each stat's label on the left and its number
+/// lined up on the right
+fn show_stats(stats: &MatchStats, frame: &mut GraphicsFrame) {
+    let (left, right) = (WIDTH / 2 - 40, WIDTH / 2 + 40);
+    let row = |i: i32| 120 + i * 8;
+    let number = |value: u32, pos: Vector2D<i32>, frame: &mut GraphicsFrame| {
+        show_number_with(&sprites::LIGHT_DIGITS, value, pos, frame)
+    };
+
+    show_text(&sprites::RALLY, vec2(left, row(0)), frame);
+    number(stats.longest_rally, vec2(right, row(0)), frame);
+
+    show_text(&sprites::HITS, vec2(left, row(1)), frame);
+    number(stats.hits, vec2(right, row(1)), frame);
+
+    // Minutes and seconds, laid out like the time attack clock
+    let seconds = seconds_for_steps(stats.frames);
+    show_text(&sprites::TIME, vec2(left, row(2)), frame);
+    number(seconds % 10, vec2(right, row(2)), frame);
+    number((seconds % 60) / 10, vec2(right - 8, row(2)), frame);
+    frame.show(Object::new(sprites::LIGHT_COLON.sprite(0)).set_pos(vec2(right - 24, row(2))));
+    number(seconds / 60, vec2(right - 24, row(2)), frame);
+
+    // Left paddle's points first, with a dash between them
+    let [a, b] = stats.score;
+    show_text(&sprites::SCORE, vec2(left, row(3)), frame);
+    let dash = number(b.into(), vec2(right, row(3)), frame) - 8;
+    frame.show(Object::new(sprites::LIGHT_MINUS.sprite(0)).set_pos(vec2(dash, row(3))));
+    number(a.into(), vec2(dash, row(3)), frame);
+}
+
+fn show_high_score(high_score: u16, frame: &mut GraphicsFrame) {
+    let mut top_left = vec2(WIDTH / 2 - 20, 104);
+
+    for i in 0..3 {
+        frame.show(Object::new(sprites::BEST.sprite(i)).set_pos(top_left));
+        top_left.x += 8;
+    }
+
+    show_number_with(
+        &sprites::LIGHT_DIGITS,
+        high_score.into(),
+        vec2(WIDTH / 2 + 20, top_left.y),
+        frame,
+    );
+}
+
+// The main function must take 1 arguments and never returns, and must be marked with
+// the #[agb::entry] macro.
+#[agb::entry]
+fn main(mut gba: agb::Gba) -> ! {
+    let mut controller = agb::input::ButtonController::new();
+
+    let mut mixer = gba.mixer.mixer(Frequency::Hz32768);
+    let mut tracker = Tracker::new(&BGM);
+
+    let mut gfx = gba.graphics.get();
+    let mut backgrounds = Backgrounds::new();
+
+    gba.save.init_sram();
+    let mut high_score = persistence::load_high_score(&mut gba.save);
+    // A match left unfinished when the console was switched off, which A carries on with from
+    // the title screen
+    let mut saved_match = persistence::load_match(&mut gba.save);
+    let mut settings = persistence::load_settings(&mut gba.save);
+    // All the backgrounds share these palettes, so they only need setting again when the theme
+    // changes
+    let mut palettes = Palettes::new(settings.theme, background::PALETTES);
+
+    // The GBA only has the one controller, so the right paddle is the CPU unless somebody wants
+    // to practise playing both sides
+    let shared_controller = false;
+    // Records every match so the last one can be watched again by pressing L on the title screen,
+    // which is handy for bug reports and for showing the game off
+    let record_replays = false;
+    let mut replays = Replays::default();
+    // Holding L and pressing SELECT mid game shows the numbers for tuning the physics
+    let mut debug_overlay = false;
+    let mut game = Game::title();
+    let mut sounds = FrameSounds::default();
+    let mut events = EventSink::default();
+    // Counts every frame since power on. How long the player takes to press START is
+    // unpredictable, so this makes a good seed for each new game.
+    let mut frame_count: u32 = 0;
+    let mut timestep = FixedTimestep::new();
+
+    loop {
+        frame_count = frame_count.wrapping_add(1);
+        // Taken every frame, so the time spent on other screens doesn't get caught up on once
+        // play starts
+        let steps = timestep.steps();
+        // Catches any sprite palettes loaded for the last frame, which has only just gone up on
+        // the screen
+        palettes.update();
+        // Any match that starts this frame uses whatever's set in the options
+        let config = settings.game_config();
+        let difficulty = settings.difficulty;
+
+        game = match game {
+            Game::Title { mut idle_frames } => {
+                controller.update();
+
+                let mut frame = gfx.frame();
+                backgrounds.title.show(&mut frame);
+                if saved_match.is_some() {
+                    show_text(&sprites::CONTINUE, vec2(WIDTH / 2 - 24, 148), &mut frame);
+                }
+
+                play_music(&mut tracker, &mut mixer, &settings);
+                mixer.frame();
+                frame.commit();
+
+                if controller.is_pressed(Button::all()) {
+                    idle_frames = 0;
+                } else {
+                    idle_frames += 1;
+                }
+
+                let replay = if record_replays && controller.is_just_pressed(Button::L) {
+                    replays.play()
+                } else {
+                    None
+                };
+
+                if let Some(setup) = replay {
+                    restart_music(&mut tracker, &mut mixer);
+                    Game::new(
+                        setup.shared_controller,
+                        setup.difficulty,
+                        setup.config,
+                        Rng::new(setup.seed),
+                        None,
+                    )
+                } else {
+                    match menu_select(&Input::from(&controller)) {
+                        // Holding L and R too plays a batch of matches CPU against CPU with
+                        // whatever's in the options, for tuning. The title stays up while it
+                        // runs, and the results go to the emulator's log.
+                        Some(MenuAction::Start)
+                            if controller.is_pressed(Button::L)
+                                && controller.is_pressed(Button::R) =>
+                        {
+                            benchmark::run(difficulty, config, frame_count, BENCHMARK_MATCHES)
+                                .log(frame_count);
+                            Game::title()
+                        }
+                        Some(MenuAction::Start) if settings.four_player => {
+                            restart_music(&mut tracker, &mut mixer);
+                            Game::four_player(difficulty, config, Rng::new(frame_count))
+                        }
+                        Some(MenuAction::Start) => {
+                            // Holding R looks for a second console to play against over the link
+                            // cable, and if there isn't one it's just a normal game
+                            let link = if controller.is_pressed(Button::R) {
+                                Link::connect()
+                            } else {
+                                None
+                            };
+                            // Link games depend on the other console too, so they can't be replayed
+                            if record_replays && link.is_none() {
+                                replays.start_recording(ReplaySetup {
+                                    seed: frame_count,
+                                    config,
+                                    difficulty,
+                                    shared_controller,
+                                });
+                            }
+
+                            restart_music(&mut tracker, &mut mixer);
+                            Game::new(
+                                shared_controller,
+                                difficulty,
+                                config,
+                                Rng::new(frame_count),
+                                link,
+                            )
+                        }
+                        Some(MenuAction::Select) => Game::options(),
+                        Some(MenuAction::Confirm) => match &saved_match {
+                            Some(snapshot) => {
+                                restart_music(&mut tracker, &mut mixer);
+                                Game::Playing(Box::new(snapshot.restore()))
+                            }
+                            None => Game::Title { idle_frames },
+                        },
+                        _ if idle_frames >= DEMO_DELAY => {
+                            Game::demo(difficulty, config, Rng::new(frame_count))
+                        }
+                        _ => Game::Title { idle_frames },
+                    }
+                }
+            }
+            Game::Options {
+                mut selected,
+                scroll,
+            } => {
+                controller.update();
+                let action = menu_select(&Input::from(&controller));
+
+                selected = selected.cycle(controller.just_pressed_y_tri() as i32);
+                let scroll = selected.scroll_to(scroll);
+
+                let changed = selected.adjust(
+                    &mut settings,
+                    controller.just_pressed_x_tri() as i32,
+                    action == Some(MenuAction::Confirm),
+                );
+                if changed && selected == OptionsItem::Theme {
+                    palettes.set_theme(settings.theme);
+                }
+                // Give a taste of the new volume
+                if changed {
+                    play_sound(
+                        &mut mixer,
+                        WALL_HIT,
+                        settings.effective_volume(),
+                        SoundCue::at(num!(WIDTH / 2)),
+                    );
+                }
+
+                let mut frame = gfx.frame();
+                backgrounds.options.show(&mut frame);
+                show_options(&settings, selected, scroll, &mut frame);
+
+                play_music(&mut tracker, &mut mixer, &settings);
+                mixer.frame();
+                frame.commit();
+
+                if action == Some(MenuAction::Back) {
+                    persistence::save_settings(&mut gba.save, &settings);
+                    Game::title()
+                } else {
+                    Game::Options { selected, scroll }
+                }
+            }
+            Game::Playing(mut match_state) => {
+                let gp = &mut match_state.game;
+                let was_paused = gp.paused.is_some();
+                let points = gp.stats.points;
+
+                controller.update();
+                let live = Input::from(&controller);
+                let replaying = replays.is_playing();
+                let replayed = replays.input(live);
+                let replay_over = replayed.is_none();
+                let input = replayed.unwrap_or_default();
+
+                // Any button stops the demo or a replay, so the demo never pauses
+                let leave_demo = (gp.demo || replaying) && live.is_just_pressed(Button::all());
+                let action = menu_select(&input);
+                // The pause menu goes by the replayed input like everything else, so a replay
+                // pauses and resumes wherever the match did
+                let picked = if gp.demo {
+                    None
+                } else {
+                    update_pause_menu(&mut gp.paused, action, &input)
+                };
+                let debug_combo =
+                    live.is_pressed(Button::L) && live.is_just_pressed(Button::SELECT);
+                if debug_combo {
+                    debug_overlay = !debug_overlay;
+                }
+                // SELECT tries out the themes while paused, to see how they look mid game
+                if gp.paused.is_some()
+                    && !replaying
+                    && !debug_combo
+                    && action == Some(MenuAction::Select)
+                {
+                    settings.theme = settings.theme.cycle(1);
+                    palettes.set_theme(settings.theme);
+                    persistence::save_settings(&mut gba.save, &settings);
+                }
+
+                // The time goes by the vblanks that have passed, before the game speed changes how
+                // many steps they make
+                if gp.paused.is_none() {
+                    gp.count_frames(steps);
+                }
+
+                // Usually one step, but more if the last frame took too long to draw so that the
+                // game doesn't slow down, or at turbo speed. Resuming waits a frame, or the A
+                // that picked it would serve the ball too.
+                let steps = replays.steps(if gp.paused.is_some() || picked.is_some() {
+                    0
+                } else {
+                    timestep.scaled(steps, settings.speed())
+                });
+                // Below full speed some frames don't step at all, and a press on one of those
+                // has to wait for the next step or a quick tap of A would never serve
+                let input = if gp.paused.is_some() || picked.is_some() {
+                    gp.presses.clear();
+                    input
+                } else {
+                    gp.presses.latch(input, steps)
+                };
+                for _ in 0..steps {
+                    let shake = gp.step(&input, &mut events, &mut sounds);
+                    backgrounds.play_field.set_scroll_pos(shake);
+
+                    // Catching up on several steps mustn't carry on past the end of the game, or
+                    // a ball could take the other side's last life after the game was already won
+                    if gp.result().is_some() {
+                        break;
+                    }
+                }
+                gp.update_link();
+                sounds.play(&mut mixer, settings.effective_volume());
+
+                let mut frame = gfx.frame();
+                backgrounds.play_field.show(&mut frame);
+                // Everything in the match goes through the budget, so it knows how much room is
+                // left for the decorations at the end
+                let mut budget = SpriteBudget::new(&mut frame);
+
+                gp.paddle_a.show(&mut budget);
+                gp.paddle_a.show_charge(&mut budget);
+                gp.show_opponent(&mut budget);
+                for ball in &gp.balls {
+                    ball.show(&mut budget);
+                }
+                gp.show_power_ups(&mut budget);
+                gp.show_obstacles(&mut budget);
+                gp.show_wind(&mut budget);
+                gp.particles.show(&mut budget);
+
+                match gp.config.win_condition {
+                    WinCondition::Lives => {
+                        gp.paddle_a.show_health(&mut budget);
+                        gp.paddle_b.show_health(&mut budget);
+                    }
+                    WinCondition::Score => {
+                        gp.paddle_a.show_score(&mut budget);
+                        gp.paddle_b.show_score(&mut budget);
+                    }
+                    WinCondition::Bricks => gp.paddle_a.show_health(&mut budget),
+                    WinCondition::TimeAttack => {
+                        gp.paddle_a.show_score(&mut budget);
+                        gp.paddle_b.show_score(&mut budget);
+                        // The clock's stuck on 0 in overtime, and the banner goes where it was
+                        if !gp.overtime {
+                            gp.show_clock(&mut budget);
+                        }
+                    }
+                }
+                if gp.overtime {
+                    gp.show_overtime(&mut budget);
+                } else if gp.is_deuce() {
+                    gp.show_deuce(&mut budget);
+                }
+                gp.show_rally(&mut budget);
+                if settings.assist && !gp.demo {
+                    gp.show_landing(&mut budget);
+                }
+                gp.show_countdown(&mut budget);
+                gp.show_paused(&mut budget);
+                if !gp.demo && !replaying {
+                    high_score = high_score.max(gp.rally().try_into().unwrap_or(u16::MAX));
+                }
+                match_state.show_games_won(&mut budget);
+                if debug_overlay {
+                    match_state
+                        .game
+                        .show_debug_overlay(frame_count, &mut budget);
+                }
+                budget.finish();
+
+                play_music(&mut tracker, &mut mixer, &settings);
+                mixer.frame();
+
+                frame.commit();
+
+                let demo = match_state.game.demo;
+                // Finishing the game sets up the next one, so this has to be checked first
+                let skunk = match_state.game.is_skunk();
+                let stats = match_state.game.stats();
+                let match_result = match_state
+                    .game
+                    .result()
+                    .and_then(|result| match_state.finish_game(result));
+
+                // A recording stops where the match was restarted or quit, so a replay that gets
+                // there is over
+                let abandoned = matches!(picked, Some(PauseItem::Restart | PauseItem::Quit));
+                if leave_demo || replay_over || (replaying && (match_result.is_some() || abandoned))
+                {
+                    replays.stop();
+                    Game::title()
+                } else if abandoned {
+                    replays.stop();
+                    if match_state.saved {
+                        saved_match = None;
+                        persistence::save_match(&mut gba.save, None);
+                    }
+                    // The best rally still counts even if the match never finished
+                    if high_score > persistence::load_high_score(&mut gba.save) {
+                        persistence::save_high_score(&mut gba.save, high_score);
+                    }
+
+                    if picked == Some(PauseItem::Restart) {
+                        if record_replays {
+                            replays.start_recording(ReplaySetup {
+                                seed: frame_count,
+                                config,
+                                difficulty,
+                                shared_controller,
+                            });
+                        }
+                        // Like any new game the music starts over. A link game starts over
+                        // against the CPU, as the other console is still busy with the old match.
+                        restart_music(&mut tracker, &mut mixer);
+                        Game::new(
+                            shared_controller,
+                            difficulty,
+                            config,
+                            Rng::new(frame_count),
+                            None,
+                        )
+                    } else {
+                        // The title screen plays the same music, so it just keeps going
+                        Game::title()
+                    }
+                } else if demo && match_result.is_some() {
+                    // The demo never ends, it just starts over
+                    Game::demo(difficulty, config, Rng::new(frame_count))
+                } else if let Some(result) = match_result {
+                    replays.stop();
+                    if match_state.saved {
+                        saved_match = None;
+                        persistence::save_match(&mut gba.save, None);
+                    }
+                    if high_score > persistence::load_high_score(&mut gba.save) {
+                        persistence::save_high_score(&mut gba.save, high_score);
+                    }
+
+                    // The game over screen is silent, the music only comes back for the next game
+                    tracker.stop(&mut mixer);
+
+                    Game::Over {
+                        result,
+                        skunk,
+                        stats: Some(stats),
+                        link: match_state.game.link.take(),
+                    }
+                } else {
+                    // Saved whenever a point ends or the game's paused, so switching off never
+                    // loses more than the point being played. A new match waits for its first
+                    // point before taking over the save, so the match saved before it isn't lost
+                    // to one that was only started.
+                    let game = &match_state.game;
+                    let just_paused = game.paused.is_some() && !was_paused;
+                    if !replaying
+                        && ((just_paused && match_state.saved) || game.stats.points != points)
+                        && let Some(snapshot) = MatchSnapshot::take(&match_state)
+                    {
+                        persistence::save_match(&mut gba.save, Some(&snapshot));
+                        saved_match = Some(snapshot);
+                        match_state.saved = true;
+                    }
+                    Game::Playing(match_state)
+                }
+            }
+            Game::FourPlayer(mut game) => {
+                controller.update();
+                let input = Input::from(&controller);
+                // Resuming waits a frame like the main game, so the A that picked it isn't taken
+                // as a move
+                let picked = game.update_pause(&input);
+                let steps = if game.is_paused() || picked.is_some() {
+                    0
+                } else {
+                    timestep.scaled(steps, settings.speed())
+                };
+
+                for _ in 0..steps {
+                    game.update(&input, &mut events);
+                    game.handle_events(&mut events, &mut sounds);
+                    if game.result().is_some() {
+                        break;
+                    }
+                }
+                sounds.play(&mut mixer, settings.effective_volume());
+
+                let mut frame = gfx.frame();
+                game.show(&mut frame);
+                backgrounds.play_field.show(&mut frame);
+
+                play_music(&mut tracker, &mut mixer, &settings);
+                mixer.frame();
+                frame.commit();
+
+                if picked == Some(PauseItem::Restart) {
+                    restart_music(&mut tracker, &mut mixer);
+                    Game::four_player(difficulty, config, Rng::new(frame_count))
+                } else if picked == Some(PauseItem::Quit) {
+                    Game::title()
+                } else if let Some(result) = game.result() {
+                    tracker.stop(&mut mixer);
+                    Game::Over {
+                        result,
+                        skunk: false,
+                        stats: None,
+                        link: None,
+                    }
+                } else {
+                    Game::FourPlayer(game)
+                }
+            }
+            Game::Over {
+                result,
+                skunk,
+                stats,
+                mut link,
+            } => {
+                controller.update();
+                if let Some(connection) = &mut link
+                    && !connection.keep_alive()
+                {
+                    link = None;
+                }
+
+                let mut frame = gfx.frame();
+                backgrounds.game_over.show(&mut frame);
+                show_result(result, &mut frame);
+                if skunk {
+                    show_skunk(&mut frame);
+                }
+                show_high_score(high_score, &mut frame);
+                if let Some(stats) = &stats {
+                    show_stats(stats, &mut frame);
+                }
 
                 mixer.frame();
                 frame.commit();
 
-                if controller.is_pressed(Button::START) {
-                    Game::new()
+                if menu_select(&Input::from(&controller)) == Some(MenuAction::Start) {
+                    restart_music(&mut tracker, &mut mixer);
+                    if settings.four_player {
+                        Game::four_player(difficulty, config, Rng::new(frame_count))
+                    } else {
+                        // Link games depend on the other console too, so they can't be replayed
+                        if record_replays && link.is_none() {
+                            replays.start_recording(ReplaySetup {
+                                seed: frame_count,
+                                config,
+                                difficulty,
+                                shared_controller,
+                            });
+                        }
+                        Game::new(
+                            shared_controller,
+                            difficulty,
+                            config,
+                            Rng::new(frame_count),
+                            link,
+                        )
+                    }
                 } else {
-                    Game::Over(bg)
+                    Game::Over {
+                        result,
+                        skunk,
+                        stats,
+                        link,
+                    }
                 }
             }
         }
@@ -0,0 +1,98 @@
+//! Turns the music down to the volume in the settings. The tracker sets the volume of each of its
+//! channels itself as the track goes, so it plays through a mixer that scales whatever it asks
+//! for on the way.
+
+use core::cell::Cell;
+
+use agb::fixnum::{FixedNum, Num};
+use agb::sound::mixer::{ChannelId, Mixer, SoundChannel};
+use alloc::borrow::Cow;
+use critical_section::Mutex;
+
+/// How loud the music plays, from 0 for silent up to 1 for as loud as the track goes. It's kept
+/// out here because the tracker only ever gets at its channels by reference, so there's nowhere
+/// on them to put it.
+static VOLUME: Mutex<Cell<FixedNum<8>>> = Mutex::new(Cell::new(FixedNum::from_raw(1 << 8)));
+
+/// The mixer as the tracker sees it, with every channel it plays turned down to `volume`
+pub struct MusicMixer<'m, 'g>(&'m mut Mixer<'g>);
+
+impl<'m, 'g> MusicMixer<'m, 'g> {
+    pub fn new(mixer: &'m mut Mixer<'g>, volume: FixedNum<8>) -> Self {
+        critical_section::with(|cs| VOLUME.borrow(cs).set(volume));
+        Self(mixer)
+    }
+}
+
+/// One of the music's channels. It's the mixer's own channel underneath, just with its volume
+/// scaled.
+#[repr(transparent)]
+pub struct MusicChannel(SoundChannel);
+
+impl agb_tracker::SoundChannel for MusicChannel {
+    fn new(data: &Cow<'static, [u8]>) -> Self {
+        Self(<SoundChannel as agb_tracker::SoundChannel>::new(data))
+    }
+
+    fn stop(&mut self) {
+        self.0.stop();
+    }
+
+    fn pause(&mut self) -> &mut Self {
+        self.0.pause();
+        self
+    }
+
+    fn resume(&mut self) -> &mut Self {
+        self.0.resume();
+        self
+    }
+
+    fn should_loop(&mut self) -> &mut Self {
+        self.0.should_loop();
+        self
+    }
+
+    fn volume(&mut self, value: impl Into<Num<i16, 8>>) -> &mut Self {
+        let volume = critical_section::with(|cs| VOLUME.borrow(cs).get());
+        self.0
+            .volume(value.into() * Num::<i16, 8>::from_raw(volume.to_raw() as i16));
+        self
+    }
+
+    fn restart_point(&mut self, value: impl Into<Num<u32, 8>>) -> &mut Self {
+        self.0.restart_point(value);
+        self
+    }
+
+    fn playback(&mut self, playback_speed: impl Into<Num<u32, 8>>) -> &mut Self {
+        self.0.playback(playback_speed);
+        self
+    }
+
+    fn panning(&mut self, panning: impl Into<Num<i16, 8>>) -> &mut Self {
+        self.0.panning(panning);
+        self
+    }
+
+    fn set_pos(&mut self, pos: impl Into<Num<u32, 8>>) -> &mut Self {
+        self.0.set_pos(pos);
+        self
+    }
+}
+
+impl agb_tracker::Mixer for MusicMixer<'_, '_> {
+    type ChannelId = ChannelId;
+    type SoundChannel = MusicChannel;
+
+    fn channel(&mut self, channel_id: &ChannelId) -> Option<&mut MusicChannel> {
+        let channel: *mut SoundChannel = self.0.channel(channel_id)?;
+        // SAFETY: `MusicChannel` is a transparent wrapper, so it's laid out the same as the
+        // channel it wraps, and the borrow is still of the mixer
+        Some(unsafe { &mut *channel.cast::<MusicChannel>() })
+    }
+
+    fn play_sound(&mut self, channel: MusicChannel) -> Option<ChannelId> {
+        self.0.play_sound(channel.0)
+    }
+}
@@ -0,0 +1,186 @@
+//! Blocks in the middle of the field that the ball bounces off.
+
+use agb::display::Priority;
+use agb::display::object::Object;
+use agb::fixnum::{FixedNum, Rect, Vector2D, num, vec2};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::sprite_budget::ObjectSink;
+use crate::{fixed, sprites};
+
+pub struct Obstacle {
+    pos: Vector2D<FixedNum<8>>,
+    /// How far the obstacle moves down each frame, negative for up
+    velocity: FixedNum<8>,
+    /// The highest and lowest the top of a moving obstacle goes before it turns around
+    travel: Option<(FixedNum<8>, FixedNum<8>)>,
+}
+
+impl Obstacle {
+    pub fn new(pos: Vector2D<FixedNum<8>>) -> Self {
+        Self {
+            pos,
+            velocity: num!(0),
+            travel: None,
+        }
+    }
+
+    /// An obstacle that slides up and down between `min_y` and `max_y`, starting off downwards
+    pub fn moving(
+        pos: Vector2D<FixedNum<8>>,
+        speed: FixedNum<8>,
+        min_y: FixedNum<8>,
+        max_y: FixedNum<8>,
+    ) -> Self {
+        Self {
+            pos,
+            velocity: speed,
+            travel: Some((min_y, max_y)),
+        }
+    }
+
+    /// Moves the obstacle on by a frame. This doesn't depend on anything but the frame count, so
+    /// it's the same in every match.
+    pub fn update(&mut self) {
+        let Some((min_y, max_y)) = self.travel else {
+            return;
+        };
+
+        self.pos.y += self.velocity;
+        if self.pos.y <= min_y || self.pos.y >= max_y {
+            self.pos.y = self.pos.y.max(min_y).min(max_y);
+            self.velocity = -self.velocity;
+        }
+    }
+
+    pub fn velocity(&self) -> Vector2D<FixedNum<8>> {
+        vec2(num!(0), self.velocity)
+    }
+
+    pub fn collision_rect(&self) -> Rect<FixedNum<8>> {
+        Rect::new(self.pos, vec2(num!(16), num!(16)))
+    }
+
+    pub fn show(&self, frame: &mut impl ObjectSink) {
+        frame.show(
+            Object::new(sprites::OBSTACLE.sprite(0))
+                .set_pos(self.pos.round())
+                .set_priority(Priority::P1),
+        );
+    }
+}
+
+/// Where the obstacles go. Apart from the hazard, none of them cover the very centre of the field,
+/// where extra balls are launched from. Balls launched while the hazard's passing through start
+/// off to the side of it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ObstacleLayout {
+    None,
+    /// A block above and below the middle of the net
+    Pillars,
+    /// A block in front of each paddle's half, offset so neither side has it easier
+    Staggered,
+    /// A single block sliding up and down the net, so shots have to be timed to get past it
+    Hazard,
+}
+
+/// How fast the hazard block slides in pixels per frame
+const HAZARD_SPEED: FixedNum<8> = fixed(0.75);
+
+/// The layouts in the order the options go through them, which is also how they're saved. No
+/// obstacles comes first, so blank memory in an older save leaves the field clear.
+const LAYOUTS: [ObstacleLayout; 4] = [
+    ObstacleLayout::None,
+    ObstacleLayout::Pillars,
+    ObstacleLayout::Staggered,
+    ObstacleLayout::Hazard,
+];
+
+impl ObstacleLayout {
+    pub fn index(self) -> u8 {
+        LAYOUTS
+            .iter()
+            .position(|&layout| layout == self)
+            .unwrap_or(0) as u8
+    }
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        LAYOUTS.get(usize::from(index)).copied()
+    }
+
+    /// The layout `steps` along the list from this one, wrapping around at either end
+    pub fn cycle(self, steps: i32) -> Self {
+        let index = (i32::from(self.index()) + steps).rem_euclid(LAYOUTS.len() as i32);
+        LAYOUTS[index as usize]
+    }
+
+    /// The blocks for this layout, placed around the middle of `court`
+    pub fn obstacles(self, court: Rect<FixedNum<8>>) -> Vec<Obstacle> {
+        let (left, top) = (court.top_left().x.floor(), court.top_left().y.floor());
+        let (width, height) = (court.size.x.floor(), court.size.y.floor());
+        let bottom = top + height;
+
+        if self == ObstacleLayout::Hazard {
+            let start = vec2(left + width / 2 - 8, top + height / 2 - 8);
+            let start = vec2(FixedNum::new(start.x), FixedNum::new(start.y));
+            let (min_y, max_y) = (FixedNum::new(top + 8), FixedNum::new(bottom - 16 - 8));
+            return vec![Obstacle::moving(start, HAZARD_SPEED, min_y, max_y)];
+        }
+
+        let positions: &[(i32, i32)] = match self {
+            ObstacleLayout::None | ObstacleLayout::Hazard => &[],
+            ObstacleLayout::Pillars => &[(width / 2 - 8, 32), (width / 2 - 8, height - 48)],
+            ObstacleLayout::Staggered => &[(width / 3 - 8, 40), (width * 2 / 3 - 8, height - 56)],
+        };
+
+        positions
+            .iter()
+            .map(|&(x, y)| Obstacle::new(vec2(FixedNum::new(left + x), FixedNum::new(top + y))))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ball::full_court;
+
+    #[test_case]
+    fn obstacles_stay_inside_a_smaller_court(_gba: &mut agb::Gba) {
+        // A scoreboard strip along the top pushes everything down
+        let full = full_court();
+        let court = Rect::new(vec2(num!(0), num!(24)), full.size - vec2(num!(0), num!(24)));
+
+        for layout in LAYOUTS {
+            for obstacle in layout.obstacles(court) {
+                let rect = obstacle.collision_rect();
+                assert!(rect.top_left().y >= court.top_left().y);
+                assert!(rect.bottom_right().y <= court.bottom_right().y);
+            }
+        }
+    }
+
+    #[test_case]
+    fn moving_obstacle_turns_around_at_its_bounds(_gba: &mut agb::Gba) {
+        let mut obstacle = Obstacle::moving(vec2(num!(100), num!(20)), num!(3), num!(10), num!(30));
+
+        let mut turned = 0;
+        let mut last_velocity = obstacle.velocity;
+        for _ in 0..100 {
+            obstacle.update();
+            assert!(
+                obstacle.pos.y >= num!(10) && obstacle.pos.y <= num!(30),
+                "{} out of bounds",
+                obstacle.pos.y
+            );
+
+            if obstacle.velocity != last_velocity {
+                turned += 1;
+                last_velocity = obstacle.velocity;
+            }
+        }
+
+        assert!(turned > 2, "the obstacle should keep going back and forth");
+    }
+}
@@ -0,0 +1,796 @@
+//! The paddles, whether they're moved by a player or by the CPU.
+
+use agb::display::object::{Object, Tag};
+use agb::display::{HEIGHT, Priority, WIDTH};
+use agb::fixnum::{FixedNum, Rect, Vector2D, num, vec2};
+
+use crate::ball::{Ball, HIT_FLASH_FRAMES, full_court};
+use crate::controller::{GameView, PaddleController, PaddleState};
+use crate::sprite_budget::ObjectSink;
+use crate::{Side, fixed, show_number, sprites};
+
+/// As many hearts as fit between the CPU's name and the edge of the screen. Any more go on
+/// another row underneath. The paddles along the top and bottom of a four player game have the
+/// middle of their edge to themselves, so theirs all go on the one row.
+const HEARTS_PER_ROW: u16 = 3;
+
+/// The fastest the player's paddle can move in pixels per frame
+pub const PADDLE_MAX_SPEED: FixedNum<8> = fixed(2.);
+/// How much the paddle speeds up every frame a direction is held
+const PADDLE_ACCELERATION: FixedNum<8> = fixed(0.25);
+/// How much the paddle slows down every frame once it's let go
+const PADDLE_FRICTION: FixedNum<8> = fixed(0.375);
+
+/// How much of a full power shot builds up every frame the charge button is held
+const CHARGE_RATE: FixedNum<8> = fixed(0.022);
+/// How long a released charge waits for the ball before it's wasted, so the button has to be let
+/// go just as the ball arrives
+const CHARGE_RELEASE_FRAMES: u8 = 12;
+
+/// How far the paddle has to get from the pixel it's drawn on before it's drawn somewhere else.
+/// Sprites only go on whole pixels, and like everything else the paddle is drawn at its position
+/// rounded to the nearest one, halves going down the screen. On its own that flickers between two
+/// pixels when the paddle changes direction or eases to a stop across a half, so the paddle holds
+/// its pixel until it's clearly moved off it. It's never drawn more than this far from where it
+/// really is, and not at all once a ball is within `SNAP_DISTANCE`.
+const SHOWN_SLACK: FixedNum<8> = fixed(0.75);
+/// How close a ball has to get to the paddle before the paddle goes back to being drawn on its
+/// nearest pixel, so that the ball's drawn touching it when they meet
+const SNAP_DISTANCE: FixedNum<8> = fixed(16.);
+/// How many different fill levels the charge gauge has
+const CHARGE_GAUGE_LEVELS: i32 = 8;
+
+/// A point is scored once the edge of the ball reaches the left or right edge of the court, and
+/// its top and bottom are walls. The court is the whole screen unless the config says otherwise.
+/// Each paddle's sprite is `PADDLE_WIDTH` wide and sits `PADDLE_INSET` in from its end of the
+/// court unless the config moves it, and it only hits the ball with a `PADDLE_HIT_WIDTH` wide
+/// strip down the middle. That keeps the two ends of the court mirror images, so neither side has
+/// further to cover than the other.
+pub const PADDLE_INSET: i32 = 8;
+const PADDLE_WIDTH: i32 = 16;
+const PADDLE_HIT_WIDTH: i32 = 10;
+/// How far down from the top of the court the paddles at either end start, however far in they
+/// are
+const PADDLE_START_Y: i32 = 8;
+
+/// Where the paddle on `side` starts on the whole screen with the usual inset, at the top of its
+/// end of the court. The paddles along the top and bottom of a four player game start in the
+/// middle, the same distance in from their edges.
+#[cfg(test)]
+pub fn start_pos(side: Side) -> Vector2D<FixedNum<8>> {
+    start_pos_in(side, full_court(), PADDLE_INSET)
+}
+
+/// Where the paddle on `side` starts in `court`, `inset` pixels in from its edge. Nearer the
+/// middle makes for a smaller court and quicker exchanges, and nearer the edge gives more time to
+/// react. It's the same at both ends, and never so far in that the paddles could pass each other.
+pub fn start_pos_in(side: Side, court: Rect<FixedNum<8>>, inset: i32) -> Vector2D<FixedNum<8>> {
+    let (top_left, bottom_right) = (court.top_left().floor(), court.bottom_right().floor());
+    let size = bottom_right - top_left;
+    let middle = top_left.x + (size.x - 16 * PADDLE_SEGMENTS) / 2;
+    let across = if side.is_horizontal() { size.y } else { size.x };
+    let inset = inset.clamp(0, across / 2 - PADDLE_WIDTH);
+
+    let (x, y) = match side {
+        Side::Left => (top_left.x + inset, top_left.y + PADDLE_START_Y),
+        Side::Right => (
+            bottom_right.x - PADDLE_WIDTH - inset,
+            top_left.y + PADDLE_START_Y,
+        ),
+        Side::Top => (middle, top_left.y + inset),
+        Side::Bottom => (middle, bottom_right.y - PADDLE_WIDTH - inset),
+    };
+    vec2(FixedNum::new(x), FixedNum::new(y))
+}
+
+/// The part of `court` the paddle on `side` of a four player game moves in, which stops short of
+/// the paddles on the edges either side of it so that none of them overlap in the corners
+pub fn four_player_track(side: Side, court: Rect<FixedNum<8>>, inset: i32) -> Rect<FixedNum<8>> {
+    let width = FixedNum::new(PADDLE_WIDTH);
+    if side.is_horizontal() {
+        let start = start_pos_in(Side::Left, court, inset).x + width;
+        let end = start_pos_in(Side::Right, court, inset).x;
+        Rect::new(
+            vec2(start, court.top_left().y),
+            vec2(end - start, court.size.y),
+        )
+    } else {
+        let start = start_pos_in(Side::Top, court, inset).y + width;
+        let end = start_pos_in(Side::Bottom, court, inset).y;
+        Rect::new(
+            vec2(court.top_left().x, start),
+            vec2(court.size.x, end - start),
+        )
+    }
+}
+
+/// How many 16 pixel segments make up a paddle, counting both ends
+const PADDLE_SEGMENTS: i32 = 3;
+/// A paddle can't be any shorter than its two ends
+const MIN_PADDLE_SEGMENTS: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+impl Difficulty {
+    /// Where the difficulty is in the list, which is also how it's saved
+    pub fn index(self) -> u8 {
+        DIFFICULTIES
+            .iter()
+            .position(|&difficulty| difficulty == self)
+            .unwrap_or(0) as u8
+    }
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        DIFFICULTIES.get(usize::from(index)).copied()
+    }
+
+    /// The difficulty `steps` along the list from this one, stopping at either end
+    pub fn change(self, steps: i32) -> Self {
+        let index = (i32::from(self.index()) + steps).clamp(0, DIFFICULTIES.len() as i32 - 1);
+        DIFFICULTIES[index as usize]
+    }
+
+    /// How many frames the AI waits before it takes another look at the ball
+    pub fn reaction_delay(self) -> u16 {
+        match self {
+            Difficulty::Easy => 12,
+            Difficulty::Medium => 4,
+            Difficulty::Hard => 1,
+        }
+    }
+    /// How far off the AI's aim can be, enough on Easy that the ball sometimes slips past
+    pub fn aim_error(self) -> FixedNum<8> {
+        match self {
+            Difficulty::Easy => num!(18),
+            Difficulty::Medium => num!(6),
+            Difficulty::Hard => num!(0),
+        }
+    }
+    /// How much faster than normal the ball starts each point
+    pub fn ball_speed(self) -> FixedNum<8> {
+        match self {
+            Difficulty::Easy => num!(0.75),
+            Difficulty::Medium => num!(1),
+            Difficulty::Hard => num!(1.25),
+        }
+    }
+    pub fn paddle_speed(self) -> FixedNum<8> {
+        match self {
+            Difficulty::Easy => num!(1.5),
+            Difficulty::Medium => num!(2),
+            Difficulty::Hard => num!(2.5),
+        }
+    }
+}
+
+pub struct Paddle {
+    side: Side,
+    pos: Vector2D<FixedNum<8>>,
+    /// The top speed of the paddle
+    speed: FixedNum<8>,
+    velocity: FixedNum<8>,
+    /// How far the paddle moved on the last frame, after being clamped to the screen
+    last_move: FixedNum<8>,
+    /// The pixel the top (or left hand end) of the paddle is drawn at along its track
+    shown: i32,
+    /// How many 16 pixel segments tall the paddle currently is, counting both ends
+    segments: i32,
+    /// The length the paddle goes back to once a temporary resize wears off
+    normal_segments: i32,
+    /// Frames left until the paddle goes back to its normal length
+    size_timer: u16,
+    /// Frames left showing the paddle lit up after a hit
+    hit_flash: u8,
+    health: u16,
+    /// How many hearts to show, the full ones being the health that's left
+    max_health: u16,
+    score: u16,
+    /// How much power shot has built up, from 0 to 1
+    charge: FixedNum<8>,
+    /// True while the charge button is held down
+    charging: bool,
+    /// Frames left for the ball to arrive after the charge button was let go
+    release_timer: u8,
+    /// The part of the screen the game's played in, which the paddle has to stay inside
+    court: Rect<FixedNum<8>>,
+    /// True if the CPU moved the paddle last, which gives it the CPU's colours
+    cpu: bool,
+}
+
+impl Paddle {
+    pub fn new(side: Side, start: Vector2D<FixedNum<8>>, speed: FixedNum<8>, health: u16) -> Self {
+        Self {
+            side,
+            pos: start,
+            speed,
+            velocity: num!(0),
+            last_move: num!(0),
+            shown: if side.is_horizontal() {
+                start.x.round()
+            } else {
+                start.y.round()
+            },
+            segments: PADDLE_SEGMENTS,
+            normal_segments: PADDLE_SEGMENTS,
+            size_timer: 0,
+            hit_flash: 0,
+            health,
+            max_health: health,
+            score: 0,
+            charge: num!(0),
+            charging: false,
+            release_timer: 0,
+            court: full_court(),
+            cpu: false,
+        }
+    }
+
+    /// Keeps the paddle inside `court`, moving it in if it's starting outside
+    pub fn with_court(mut self, court: Rect<FixedNum<8>>) -> Self {
+        self.court = court;
+        self.set_along(self.along());
+        self.shown = self.along().round();
+        self
+    }
+
+    /// Builds up the power shot while `held`, then gives the ball a short time to arrive once
+    /// it's let go before the charge is lost
+    pub fn update_charge(&mut self, held: bool) {
+        if held {
+            self.charge = (self.charge + CHARGE_RATE).min(num!(1));
+            self.release_timer = CHARGE_RELEASE_FRAMES;
+        } else if self.release_timer > 0 {
+            self.release_timer -= 1;
+            if self.release_timer == 0 {
+                self.charge = num!(0);
+            }
+        }
+        self.charging = held;
+    }
+
+    /// Spends the power shot on a hit, returning how charged it was from 0 to 1. It only goes off
+    /// if the charge button has been let go, so a hit while still charging gets nothing.
+    pub fn release_shot(&mut self) -> FixedNum<8> {
+        if self.charging {
+            return num!(0);
+        }
+
+        self.release_timer = 0;
+        core::mem::replace(&mut self.charge, num!(0))
+    }
+
+    pub fn health(&self) -> u16 {
+        self.health
+    }
+
+    pub fn score(&self) -> u16 {
+        self.score
+    }
+
+    /// Called when the paddle misses the ball, and the other side gets the point
+    pub fn lose_life(&mut self) {
+        self.health = self.health.saturating_sub(1);
+    }
+
+    pub fn add_point(&mut self) {
+        self.score += 1;
+    }
+
+    /// Takes on the score and health the host of a link game sent over
+    pub fn sync(&mut self, score: u16, health: u16) {
+        self.score = score;
+        self.health = health;
+    }
+
+    /// Puts the paddle back how it was in a saved match
+    pub fn resume(
+        &mut self,
+        pos: Vector2D<FixedNum<8>>,
+        velocity: FixedNum<8>,
+        score: u16,
+        health: u16,
+    ) {
+        self.pos = pos;
+        self.set_along(self.along());
+        self.shown = self.along().round();
+        self.velocity = velocity;
+        self.sync(score, health);
+    }
+
+    /// Gives back one heart, up to however many the paddle started with
+    pub fn heal(&mut self) {
+        if self.health < self.max_health {
+            self.health += 1;
+        }
+    }
+
+    /// Moves the paddle along its track, down the screen for a positive `distance`, or to the
+    /// right for one lying flat at the top or bottom
+    pub fn move_by(&mut self, distance: FixedNum<8>) {
+        let old = self.along();
+        self.set_along(old + distance);
+        self.last_move = self.along() - old;
+        self.update_shown();
+
+        // Running into the end of the track stops the paddle dead
+        if self.last_move != distance {
+            self.velocity = num!(0);
+        }
+    }
+
+    /// How far the paddle is along its track, which is the y position of the top of a paddle at
+    /// either end of the court and the x position of the left of one at the top or bottom
+    fn along(&self) -> FixedNum<8> {
+        if self.side.is_horizontal() {
+            self.pos.x
+        } else {
+            self.pos.y
+        }
+    }
+
+    /// Puts the paddle `along` its track, as far as it goes while staying in the court
+    fn set_along(&mut self, along: FixedNum<8>) {
+        let along = along.max(self.min_along()).min(self.max_along());
+        if self.side.is_horizontal() {
+            self.pos.x = along;
+        } else {
+            self.pos.y = along;
+        }
+    }
+
+    /// The length of the paddle's sprites in pixels, from end to end
+    fn length(&self) -> i32 {
+        16 * self.segments
+    }
+
+    /// Where the paddle's track starts, at the top or left hand edge of the court
+    fn min_along(&self) -> FixedNum<8> {
+        if self.side.is_horizontal() {
+            self.court.top_left().x
+        } else {
+            self.court.top_left().y
+        }
+    }
+
+    /// The furthest along its track the paddle can go while keeping it all in the court
+    fn max_along(&self) -> FixedNum<8> {
+        let end = if self.side.is_horizontal() {
+            self.court.bottom_right().x
+        } else {
+            self.court.bottom_right().y
+        };
+        end - FixedNum::new(self.length())
+    }
+
+    /// Makes the paddle `change` segments longer (or shorter if negative) than normal for
+    /// `duration` frames
+    pub fn resize_for(&mut self, change: i32, duration: u16) {
+        self.resize(self.normal_segments + change, duration);
+    }
+
+    fn resize(&mut self, segments: i32, duration: u16) {
+        let old_length = self.length();
+        self.segments = segments.max(MIN_PADDLE_SEGMENTS);
+        self.size_timer = duration;
+
+        // Grow or shrink around the middle of the paddle, then make sure it's still on the screen
+        self.set_along(self.along() - FixedNum::new(self.length() - old_length) / 2);
+        self.update_shown();
+    }
+
+    /// Moves the paddle's sprites to the nearest pixel once it's more than [`SHOWN_SLACK`] away
+    /// from where they are
+    fn update_shown(&mut self) {
+        if (self.along() - FixedNum::new(self.shown)).abs() >= SHOWN_SLACK {
+            self.shown = self.along().round();
+        }
+    }
+
+    /// Where the top left of the paddle is drawn on the screen
+    pub fn screen_pos(&self) -> Vector2D<i32> {
+        if self.side.is_horizontal() {
+            vec2(self.shown, self.pos.y.round())
+        } else {
+            vec2(self.pos.x.round(), self.shown)
+        }
+    }
+
+    /// Counts down any temporary change to the paddle's size
+    pub fn update_size(&mut self) {
+        if self.size_timer > 0 {
+            self.size_timer -= 1;
+            if self.size_timer == 0 {
+                self.resize(self.normal_segments, 0);
+            }
+        }
+    }
+
+    /// Which end of the court the paddle is at
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// The top left of the paddle's sprites
+    pub fn pos(&self) -> Vector2D<FixedNum<8>> {
+        self.pos
+    }
+
+    pub fn velocity(&self) -> FixedNum<8> {
+        self.velocity
+    }
+
+    pub fn last_move(&self) -> FixedNum<8> {
+        self.last_move
+    }
+
+    /// Where the paddle is and how it's moving, as the controller across the court sees it
+    pub fn state(&self) -> PaddleState {
+        PaddleState {
+            rect: self.collision_rect(),
+            velocity: self.velocity,
+        }
+    }
+
+    /// The part of the paddle that hits the ball, a strip down the middle of it that stops short
+    /// of the rounded ends
+    pub fn collision_rect(&self) -> Rect<FixedNum<8>> {
+        let inset = FixedNum::new((PADDLE_WIDTH - PADDLE_HIT_WIDTH) / 2);
+        let thickness = FixedNum::new(PADDLE_HIT_WIDTH);
+        let length = FixedNum::new(self.length() - 8);
+        if self.side.is_horizontal() {
+            Rect::new(self.pos + vec2(num!(4), inset), vec2(length, thickness))
+        } else {
+            Rect::new(self.pos + vec2(inset, num!(4)), vec2(thickness, length))
+        }
+    }
+    /// Lights the paddle up for a few frames after it hits the ball
+    pub fn flash(&mut self) {
+        self.hit_flash = HIT_FLASH_FRAMES;
+    }
+    /// Moves the paddle whichever way `controller` wants it to go, letting it see the `balls` and
+    /// the `opponent` across the court
+    pub fn update(
+        &mut self,
+        controller: &mut impl PaddleController,
+        balls: &[Ball],
+        opponent: Option<PaddleState>,
+    ) {
+        self.hit_flash = self.hit_flash.saturating_sub(1);
+        self.cpu = controller.is_cpu();
+
+        let view = GameView::new(self.side(), self.collision_rect(), balls).with_opponent(opponent);
+        let desired = controller.desired_move(&view);
+        if desired < num!(0) {
+            self.velocity = (self.velocity - PADDLE_ACCELERATION).max(-self.speed);
+        } else if desired > num!(0) {
+            self.velocity = (self.velocity + PADDLE_ACCELERATION).min(self.speed);
+        } else {
+            // Slow down towards a stop without overshooting into the other direction
+            self.velocity = if self.velocity > PADDLE_FRICTION {
+                self.velocity - PADDLE_FRICTION
+            } else if self.velocity < -PADDLE_FRICTION {
+                self.velocity + PADDLE_FRICTION
+            } else {
+                num!(0)
+            };
+        }
+        self.move_by(self.velocity);
+
+        if balls.iter().any(|ball| self.is_near(ball)) {
+            self.shown = self.along().round();
+        }
+    }
+
+    /// Whether `ball` is within [`SNAP_DISTANCE`] of the part of the paddle that hits it
+    fn is_near(&self, ball: &Ball) -> bool {
+        let rect = self.collision_rect();
+        let margin = vec2(SNAP_DISTANCE, SNAP_DISTANCE);
+        Rect::new(rect.position - margin, rect.size + margin * 2).contains_point(ball.pos())
+    }
+    fn show_health_from(&self, from: Vector2D<i32>, frame: &mut impl ObjectSink) {
+        // Anything that pushes the health past the max still gets a heart for it
+        let hearts = self.max_health.max(self.health);
+        let per_row = if self.side.is_horizontal() {
+            hearts.max(1)
+        } else {
+            HEARTS_PER_ROW
+        };
+        for i in 0..hearts {
+            let heart_frame = if i < self.health { 0 } else { 1 };
+            let row = i32::from(i / per_row);
+            let column = i32::from(i % per_row);
+
+            frame.show(
+                Object::new(sprites::HEART.sprite(heart_frame))
+                    .set_pos(from + vec2(column * 8, row * 8)),
+            );
+        }
+    }
+    /// Draws the paddle, facing into the court from its side
+    pub fn show(&self, frame: &mut impl ObjectSink) {
+        let pos = self.screen_pos();
+        let horizontal = self.side.is_horizontal();
+        // The CPU's paddles have their own colours so a player's paddle stands out from them,
+        // whichever side it's on, and the flat paddles have their own sprites, drawn facing down
+        // from the top. Those are only ever the CPU's. They all light up the same way after a hit.
+        let (end, mid) = match (self.side, self.hit_flash > 0) {
+            (Side::Left | Side::Right, false) if self.cpu => {
+                (&sprites::CPU_PADDLE_END, &sprites::CPU_PADDLE_MID)
+            }
+            (Side::Left | Side::Right, false) => (&sprites::PADDLE_END, &sprites::PADDLE_MID),
+            (Side::Left | Side::Right, true) => {
+                (&sprites::PADDLE_END_FLASH, &sprites::PADDLE_MID_FLASH)
+            }
+            (Side::Top | Side::Bottom, false) => (
+                &sprites::PADDLE_END_HORIZONTAL,
+                &sprites::PADDLE_MID_HORIZONTAL,
+            ),
+            (Side::Top | Side::Bottom, true) => (
+                &sprites::PADDLE_END_HORIZONTAL_FLASH,
+                &sprites::PADDLE_MID_HORIZONTAL_FLASH,
+            ),
+        };
+        let step = if horizontal { vec2(16, 0) } else { vec2(0, 16) };
+
+        // Flipping across the paddle turns it round to face the other way, and flipping along it
+        // makes the far end out of the same sprite as the near one
+        let facing_back = matches!(self.side, Side::Right | Side::Bottom);
+        let mut draw = |tag: &'static Tag, at: i32, far_end: bool| {
+            let (h_flip, v_flip) = if horizontal {
+                (far_end, facing_back)
+            } else {
+                (facing_back, far_end)
+            };
+            frame.show(
+                Object::new(tag.sprite(0))
+                    .set_pos(pos + step * at)
+                    .set_priority(Priority::P1)
+                    .set_hflip(h_flip)
+                    .set_vflip(v_flip),
+            );
+        };
+
+        draw(end, 0, false);
+        let mid_sections = self.segments - 2;
+        for i in 1..=mid_sections {
+            draw(mid, i, false);
+        }
+        draw(end, mid_sections + 1, true);
+    }
+
+    /// Where the name goes, in the top corner on the paddle's side, or the middle of the top or
+    /// bottom edge for the extra paddles in a four player game. Those go right against the edge,
+    /// in the gap between it and the paddle, so they're never drawn over the paddle.
+    fn name_pos(&self) -> Vector2D<i32> {
+        let middle = WIDTH / 2 - (8 * 5 + 3 * 2) / 2;
+        match self.side {
+            Side::Left => vec2(3, 4),
+            Side::Right => vec2(WIDTH - (8 * 5 + 3 * 2), 4),
+            Side::Top => vec2(middle, 0),
+            Side::Bottom => vec2(middle, HEIGHT - 8),
+        }
+    }
+
+    /// Displays the text `PLayer:` on the left or `CPU:` for everyone else, and returns where it
+    /// finished
+    fn show_name(&self, frame: &mut impl ObjectSink) -> Vector2D<i32> {
+        let (name, letters) = match self.side {
+            Side::Left => (&sprites::PLAYER, 4),
+            Side::Right | Side::Top | Side::Bottom => (&sprites::CPU, 2),
+        };
+        let mut top_left = self.name_pos();
+
+        for i in 0..letters {
+            frame.show(Object::new(name.sprite(i)).set_pos(top_left));
+            top_left.x += 8;
+        }
+
+        top_left
+    }
+    pub fn show_health(&self, frame: &mut impl ObjectSink) {
+        let top_left = self.show_name(frame);
+        self.show_health_from(top_left + vec2(3, 0), frame);
+    }
+    pub fn show_score(&self, frame: &mut impl ObjectSink) {
+        let top_left = self.show_name(frame);
+        show_number(self.score.into(), top_left + vec2(3 + 16, 0), frame);
+    }
+    /// Draws the power shot gauge under the paddle's name, while there's any charge
+    pub fn show_charge(&self, frame: &mut impl ObjectSink) {
+        if self.charge == num!(0) {
+            return;
+        }
+
+        let level = (self.charge * (CHARGE_GAUGE_LEVELS - 1)).floor();
+        frame.show(
+            Object::new(sprites::CHARGE.sprite(level as usize))
+                .set_pos(self.name_pos() + vec2(0, 8)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{AiController, HumanController};
+    use crate::input::Input;
+    use agb::input::Button;
+
+    #[test_case]
+    fn four_player_paddles_stay_out_of_the_corners(_gba: &mut agb::Gba) {
+        let court = full_court();
+        let paddle = |side| {
+            let (start, track) = (
+                start_pos_in(side, court, PADDLE_INSET),
+                four_player_track(side, court, PADDLE_INSET),
+            );
+            Paddle::new(side, start, PADDLE_MAX_SPEED, 3).with_court(track)
+        };
+        let sprite_rect = |paddle: &Paddle| {
+            let (length, width) = (FixedNum::new(paddle.length()), FixedNum::new(PADDLE_WIDTH));
+            let size = if paddle.side().is_horizontal() {
+                vec2(length, width)
+            } else {
+                vec2(width, length)
+            };
+            Rect::new(paddle.pos(), size)
+        };
+
+        let sides = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+        for side in sides {
+            for distance in [num!(-1000), num!(1000)] {
+                let mut moved = paddle(side);
+                moved.move_by(distance);
+                for other in sides.map(paddle) {
+                    if other.side().is_horizontal() != side.is_horizontal() {
+                        assert!(
+                            !sprite_rect(&moved).touches(sprite_rect(&other)),
+                            "a paddle at the end of its track shouldn't overlap the next one"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test_case]
+    fn both_paddles_move_the_same_way(_gba: &mut agb::Gba) {
+        let mut paddles = [Side::Left, Side::Right]
+            .map(|side| Paddle::new(side, start_pos(side), PADDLE_MAX_SPEED, 3));
+        let down = Input::new(Button::DOWN, Button::DOWN);
+        for _ in 0..10 {
+            for paddle in &mut paddles {
+                paddle.update(&mut HumanController::dpad(&down), &[], None);
+            }
+        }
+
+        let [left, right] = &paddles;
+        assert!(left.side() == Side::Left && right.side() == Side::Right);
+        assert!(left.pos().y > start_pos(Side::Left).y);
+        assert_eq!(left.pos().y, right.pos().y);
+        assert_eq!(left.last_move(), right.last_move());
+        assert_eq!(
+            left.screen_pos().x,
+            WIDTH - PADDLE_WIDTH - right.screen_pos().x,
+            "the paddles should be drawn mirrored"
+        );
+    }
+
+    #[test_case]
+    fn paddles_start_the_same_way_in_from_either_end(_gba: &mut agb::Gba) {
+        let court = Rect::new(vec2(num!(20), num!(0)), vec2(num!(200), num!(HEIGHT)));
+        let [left, right] = [Side::Left, Side::Right]
+            .map(|side| Paddle::new(side, start_pos_in(side, court, 40), PADDLE_MAX_SPEED, 3));
+
+        assert_eq!(left.collision_rect().top_left().x, num!(20 + 40 + 3));
+        assert_eq!(
+            start_pos_in(Side::Left, court, 40).y,
+            num!(PADDLE_START_Y),
+            "the inset only moves them in, not down"
+        );
+        assert_eq!(
+            start_pos_in(Side::Right, court, 40).y,
+            start_pos_in(Side::Left, court, 40).y
+        );
+        assert_eq!(
+            left.collision_rect().top_left().x - court.top_left().x,
+            court.bottom_right().x - right.collision_rect().bottom_right().x,
+            "neither end should have further to go"
+        );
+
+        // However far in they're asked to go they stay on their own halves
+        let [left, right] = [Side::Left, Side::Right].map(|side| start_pos_in(side, court, 500));
+        assert!(left.x + num!(PADDLE_WIDTH) <= right.x);
+    }
+
+    #[test_case]
+    fn paddle_wobbling_across_half_a_pixel_stays_still(_gba: &mut agb::Gba) {
+        let mut paddle = Paddle::new(Side::Left, vec2(num!(8), num!(20.375)), num!(2), 3);
+        let start = paddle.screen_pos();
+
+        for step in [
+            num!(0.25),
+            num!(-0.25),
+            num!(0.25),
+            num!(-0.125),
+            num!(0.125),
+        ] {
+            paddle.move_by(step);
+            assert_eq!(paddle.screen_pos(), start, "the paddle shouldn't flicker");
+        }
+
+        paddle.move_by(num!(1));
+        assert_eq!(paddle.screen_pos(), start + vec2(0, 2));
+    }
+
+    #[test_case]
+    fn paddle_is_drawn_where_it_is_with_the_ball_close(_gba: &mut agb::Gba) {
+        let mut paddle = Paddle::new(Side::Left, vec2(num!(8), num!(20.375)), num!(2), 3);
+        let start = paddle.screen_pos();
+        paddle.move_by(num!(0.25));
+        assert_eq!(
+            paddle.screen_pos(),
+            start,
+            "the slack holds it on its old pixel"
+        );
+
+        let ball = Ball::new(
+            paddle.pos() + vec2(num!(12), num!(20)),
+            vec2(num!(-2), num!(0)),
+        );
+        paddle.update(&mut HumanController::dpad(&Input::default()), &[ball], None);
+        assert_eq!(
+            paddle.screen_pos(),
+            start + vec2(0, 1),
+            "with the ball close it should be drawn on its nearest pixel"
+        );
+    }
+
+    #[test_case]
+    fn paddle_takes_its_colours_from_whoever_moves_it(_gba: &mut agb::Gba) {
+        // The right paddle's a person's in shared controller games, and the left one's the CPU's
+        // in the demo
+        let mut right = Paddle::new(Side::Right, start_pos(Side::Right), num!(2), 3);
+        right.update(
+            &mut HumanController::face_buttons(&Input::default()),
+            &[],
+            None,
+        );
+        assert!(!right.cpu);
+
+        let ball = Ball::new(vec2(num!(100), num!(80)), vec2(num!(-2), num!(0)));
+        let mut left = Paddle::new(Side::Left, start_pos(Side::Left), num!(2), 3);
+        left.update(&mut AiController::new(Difficulty::Medium), &[ball], None);
+        assert!(left.cpu);
+    }
+
+    #[test_case]
+    fn paddle_stays_inside_the_court(_gba: &mut agb::Gba) {
+        // A scoreboard strip along the top and a bit of a border along the bottom
+        let court = Rect::new(vec2(num!(0), num!(24)), vec2(num!(WIDTH), num!(128)));
+        let mut paddle =
+            Paddle::new(Side::Left, start_pos(Side::Left), PADDLE_MAX_SPEED, 3).with_court(court);
+        assert_eq!(
+            paddle.pos().y,
+            num!(24),
+            "the paddle should start inside the court"
+        );
+
+        paddle.move_by(num!(-10));
+        assert_eq!(paddle.pos().y, num!(24));
+
+        paddle.move_by(num!(500));
+        assert_eq!(
+            paddle.pos().y + FixedNum::new(paddle.length()),
+            num!(152),
+            "the paddle should stop at the bottom of the court"
+        );
+    }
+}
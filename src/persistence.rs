@@ -0,0 +1,193 @@
+//! Keeps the best rally and the player's settings across power cycles by storing them in the
+//! cartridge's SRAM.
+
+use agb::fixnum::FixedNum;
+use agb::save::SaveManager;
+
+use crate::ball::EdgeMode;
+use crate::obstacle::ObstacleLayout;
+use crate::paddle::Difficulty;
+use crate::snapshot::{MatchSnapshot, SNAPSHOT_LEN};
+use crate::theme::Theme;
+use crate::{GameSpeed, LIVES_CHOICES, Settings, VOLUME_STEP, WinCondition};
+
+/// Written at the start of the save so we can tell our data apart from uninitialised memory,
+/// which could contain anything.
+const MAGIC: &[u8; 4] = b"PONG";
+const HIGH_SCORE_OFFSET: usize = MAGIC.len();
+/// The volume is stored as how many steps down from full it is, so that the blank memory past the
+/// end of a save from before settings existed loads as full volume rather than silence.
+const VOLUME_OFFSET: usize = HIGH_SCORE_OFFSET + 2;
+const MUTED_OFFSET: usize = VOLUME_OFFSET + 1;
+const THEME_OFFSET: usize = MUTED_OFFSET + 1;
+/// The game speed is stored one up from its index like the difficulty, so that blank memory
+/// leaves it at full speed rather than the slowest
+const SPEED_OFFSET: usize = THEME_OFFSET + 1;
+const LARGE_BALL_OFFSET: usize = SPEED_OFFSET + 1;
+const ASSIST_OFFSET: usize = LARGE_BALL_OFFSET + 1;
+/// The difficulty is stored one up from its index, so that blank memory in a save from before it
+/// could be changed leaves it on the default
+const DIFFICULTY_OFFSET: usize = ASSIST_OFFSET + 1;
+const EDGE_MODE_OFFSET: usize = DIFFICULTY_OFFSET + 1;
+/// Stored as whether it's been turned off, so blank memory in an older save leaves it on
+const NO_MERCY_RULE_OFFSET: usize = EDGE_MODE_OFFSET + 1;
+const LIVES_OFFSET: usize = NO_MERCY_RULE_OFFSET + 1;
+const FOUR_PLAYER_OFFSET: usize = LIVES_OFFSET + 1;
+const RUBBER_BAND_OFFSET: usize = FOUR_PLAYER_OFFSET + 1;
+const WIN_CONDITION_OFFSET: usize = RUBBER_BAND_OFFSET + 1;
+const MULTIBALL_OFFSET: usize = WIN_CONDITION_OFFSET + 1;
+const OBSTACLES_OFFSET: usize = MULTIBALL_OFFSET + 1;
+const GRAVITY_OFFSET: usize = OBSTACLES_OFFSET + 1;
+const WIND_OFFSET: usize = GRAVITY_OFFSET + 1;
+const TURBO_OFFSET: usize = WIND_OFFSET + 1;
+const SLOW_PRACTICE_OFFSET: usize = TURBO_OFFSET + 1;
+const SAVE_LEN: usize = SLOW_PRACTICE_OFFSET + 1;
+/// Where a match in progress is kept, well clear of the settings so they've room to grow. It has
+/// its own header, so it can come and go without touching anything else.
+const MATCH_OFFSET: usize = 64;
+
+/// Reads the whole save, or `None` if there isn't one or the save media can't be accessed.
+fn read(save: &mut SaveManager) -> Option<[u8; SAVE_LEN]> {
+    let mut data = save.access().ok()?;
+
+    let mut buffer = [0; SAVE_LEN];
+    if data.read(0, &mut buffer).is_err() || &buffer[..HIGH_SCORE_OFFSET] != MAGIC {
+        return None;
+    }
+
+    Some(buffer)
+}
+
+/// Changes part of the save while keeping everything else in it, starting a fresh one if there
+/// wasn't one already.
+fn update(save: &mut SaveManager, change: impl FnOnce(&mut [u8; SAVE_LEN])) {
+    let mut buffer = read(save).unwrap_or_else(|| {
+        let mut buffer = [0; SAVE_LEN];
+        buffer[..HIGH_SCORE_OFFSET].copy_from_slice(MAGIC);
+        buffer
+    });
+    change(&mut buffer);
+
+    // If the write fails there's nothing useful to do about it, the change just won't stick
+    let Ok(mut data) = save.access() else {
+        return;
+    };
+    if let Ok(mut block) = data.prepare_write(0..SAVE_LEN) {
+        let _ = block.write(0, &buffer);
+    }
+}
+
+/// Reads the stored high score, falling back to 0 if there isn't one or the save media can't be
+/// accessed.
+pub fn load_high_score(save: &mut SaveManager) -> u16 {
+    let Some(buffer) = read(save) else {
+        return 0;
+    };
+
+    u16::from_le_bytes([buffer[HIGH_SCORE_OFFSET], buffer[HIGH_SCORE_OFFSET + 1]])
+}
+
+pub fn save_high_score(save: &mut SaveManager, value: u16) {
+    update(save, |buffer| {
+        buffer[HIGH_SCORE_OFFSET..VOLUME_OFFSET].copy_from_slice(&value.to_le_bytes());
+    });
+}
+
+/// Reads the match that was left unfinished, if there is one from this version of the game
+pub fn load_match(save: &mut SaveManager) -> Option<MatchSnapshot> {
+    let mut data = save.access().ok()?;
+
+    let mut buffer = [0; SNAPSHOT_LEN];
+    data.read(MATCH_OFFSET, &mut buffer).ok()?;
+    MatchSnapshot::deserialize(&buffer)
+}
+
+/// Keeps `snapshot` to carry on with later, or forgets the saved match if it's None
+pub fn save_match(save: &mut SaveManager, snapshot: Option<&MatchSnapshot>) {
+    // Blank memory doesn't have the header, so it never loads as a match
+    let buffer = snapshot.map_or([0; SNAPSHOT_LEN], MatchSnapshot::serialize);
+
+    let Ok(mut data) = save.access() else {
+        return;
+    };
+    if let Ok(mut block) = data.prepare_write(MATCH_OFFSET..MATCH_OFFSET + SNAPSHOT_LEN) {
+        let _ = block.write(MATCH_OFFSET, &buffer);
+    }
+}
+
+/// Reads the stored settings, using the defaults for anything that's missing or doesn't make
+/// sense.
+pub fn load_settings(save: &mut SaveManager) -> Settings {
+    let mut settings = Settings::default();
+    let Some(buffer) = read(save) else {
+        return settings;
+    };
+
+    let steps_down = FixedNum::new(buffer[VOLUME_OFFSET].into()) * VOLUME_STEP;
+    if steps_down <= settings.volume {
+        settings.volume -= steps_down;
+    }
+    settings.muted = buffer[MUTED_OFFSET] == 1;
+    if let Some(theme) = Theme::from_index(buffer[THEME_OFFSET]) {
+        settings.theme = theme;
+    }
+    let speed = buffer[SPEED_OFFSET].checked_sub(1);
+    if let Some(speed) = speed.and_then(GameSpeed::from_index) {
+        settings.game_speed = speed;
+    }
+    settings.large_ball = buffer[LARGE_BALL_OFFSET] == 1;
+    settings.assist = buffer[ASSIST_OFFSET] == 1;
+    let difficulty = buffer[DIFFICULTY_OFFSET].checked_sub(1);
+    if let Some(difficulty) = difficulty.and_then(Difficulty::from_index) {
+        settings.difficulty = difficulty;
+    }
+    if buffer[EDGE_MODE_OFFSET] == 1 {
+        settings.edge_mode = EdgeMode::Wrap;
+    }
+    settings.mercy_rule = buffer[NO_MERCY_RULE_OFFSET] != 1;
+    let lives = u16::from(buffer[LIVES_OFFSET]);
+    if LIVES_CHOICES.contains(&lives) {
+        settings.lives = lives;
+    }
+    settings.four_player = buffer[FOUR_PLAYER_OFFSET] == 1;
+    settings.rubber_band = buffer[RUBBER_BAND_OFFSET] == 1;
+    if let Some(win_condition) = WinCondition::from_index(buffer[WIN_CONDITION_OFFSET]) {
+        settings.win_condition = win_condition;
+    }
+    settings.multiball = buffer[MULTIBALL_OFFSET] == 1;
+    if let Some(obstacles) = ObstacleLayout::from_index(buffer[OBSTACLES_OFFSET]) {
+        settings.obstacles = obstacles;
+    }
+    settings.gravity = buffer[GRAVITY_OFFSET] == 1;
+    settings.wind = buffer[WIND_OFFSET] == 1;
+    settings.turbo = buffer[TURBO_OFFSET] == 1;
+    settings.slow_practice = buffer[SLOW_PRACTICE_OFFSET] == 1 && !settings.turbo;
+
+    settings
+}
+
+pub fn save_settings(save: &mut SaveManager, settings: &Settings) {
+    let steps_down = ((Settings::default().volume - settings.volume) / VOLUME_STEP).floor();
+
+    update(save, |buffer| {
+        buffer[VOLUME_OFFSET] = steps_down as u8;
+        buffer[MUTED_OFFSET] = settings.muted.into();
+        buffer[THEME_OFFSET] = settings.theme.index();
+        buffer[SPEED_OFFSET] = settings.game_speed.index() + 1;
+        buffer[LARGE_BALL_OFFSET] = settings.large_ball.into();
+        buffer[ASSIST_OFFSET] = settings.assist.into();
+        buffer[DIFFICULTY_OFFSET] = settings.difficulty.index() + 1;
+        buffer[EDGE_MODE_OFFSET] = (settings.edge_mode == EdgeMode::Wrap).into();
+        buffer[NO_MERCY_RULE_OFFSET] = (!settings.mercy_rule).into();
+        buffer[LIVES_OFFSET] = settings.lives as u8;
+        buffer[FOUR_PLAYER_OFFSET] = settings.four_player.into();
+        buffer[RUBBER_BAND_OFFSET] = settings.rubber_band.into();
+        buffer[WIN_CONDITION_OFFSET] = settings.win_condition.index();
+        buffer[MULTIBALL_OFFSET] = settings.multiball.into();
+        buffer[OBSTACLES_OFFSET] = settings.obstacles.index();
+        buffer[GRAVITY_OFFSET] = settings.gravity.into();
+        buffer[WIND_OFFSET] = settings.wind.into();
+        buffer[TURBO_OFFSET] = settings.turbo.into();
+        buffer[SLOW_PRACTICE_OFFSET] = settings.slow_practice.into();
+    });
+}
@@ -0,0 +1,192 @@
+//! Four player games, with a paddle guarding every edge of the screen. The player has the left
+//! paddle and the CPU plays the other three. Each time the ball gets past a paddle it loses a
+//! life, and once a paddle's out of lives its edge is walled off. The last paddle left wins.
+
+use agb::display::GraphicsFrame;
+use agb::fixnum::{num, vec2};
+use agb::input::Button;
+
+use crate::ball::Ball;
+use crate::controller::{AiController, HumanController};
+use crate::event::{EventSink, GameEvent};
+use crate::input::Input;
+use crate::paddle::{Difficulty, Paddle, four_player_track, start_pos_in};
+use crate::rng::Rng;
+use crate::sprite_budget::SpriteBudget;
+use crate::{
+    COUNTDOWN_LENGTH, FrameSounds, GameConfig, MatchResult, PauseItem, Side, SoundCue, menu_select,
+    show_countdown, show_pause_menu, update_pause_menu,
+};
+
+/// The order the paddles are kept in, with the player's first
+const SIDES: [Side; 4] = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+
+pub struct FourPlayerGame {
+    paddles: [Paddle; 4],
+    /// The CPU players for every paddle but the player's, in the same order as `paddles`
+    ais: [AiController; 3],
+    ball: Ball,
+    /// Frames left in the countdown before the ball goes
+    countdown: u16,
+    rng: Rng,
+    /// The pause menu works the same as in a two player match, with nothing moving underneath
+    paused: Option<PauseItem>,
+}
+
+impl FourPlayerGame {
+    pub fn new(difficulty: Difficulty, config: GameConfig, mut rng: Rng) -> Self {
+        let paddles = SIDES.map(|side| {
+            let speed = if side == Side::Left {
+                config.paddle_speed
+            } else {
+                difficulty.paddle_speed()
+            };
+            let pos = start_pos_in(side, config.court, config.paddle_inset);
+            let track = four_player_track(side, config.court, config.paddle_inset);
+            Paddle::new(side, pos, speed, config.starting_health).with_court(track)
+        });
+
+        let mut ball = Ball::new(vec2(num!(0), num!(0)), config.ball_velocity)
+            .with_court(config.court)
+            .with_radius(config.ball_radius);
+        let toward = SIDES[(rng.next_u32() % 4) as usize];
+        ball.serve_from_middle(&mut rng, toward);
+
+        Self {
+            paddles,
+            ais: core::array::from_fn(|_| AiController::new(difficulty)),
+            ball,
+            countdown: COUNTDOWN_LENGTH,
+            rng,
+            paused: None,
+        }
+    }
+
+    /// Opens and closes the pause menu with START, returning whatever was picked on it
+    pub fn update_pause(&mut self, input: &Input) -> Option<PauseItem> {
+        update_pause_menu(&mut self.paused, menu_select(input), input)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Moves everything on by a step
+    pub fn update(&mut self, input: &Input, events: &mut EventSink) {
+        // Each paddle sees the one on the opposite edge, which `SIDES` keeps next to it
+        let states = self
+            .paddles
+            .each_ref()
+            .map(|paddle| (paddle.health() > 0).then(|| paddle.state()));
+        let opponent = |i: usize| states[i ^ 1];
+
+        let [player, others @ ..] = &mut self.paddles;
+        let balls = core::slice::from_ref(&self.ball);
+        player.update(&mut HumanController::dpad(input), balls, opponent(0));
+        player.update_charge(input.is_pressed(Button::R));
+        for (i, (paddle, ai)) in others.iter_mut().zip(&mut self.ais).enumerate() {
+            if paddle.health() > 0 {
+                paddle.update(ai, balls, opponent(i + 1));
+            }
+        }
+
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            if self.countdown == 0 {
+                self.ball.launch();
+            }
+            return;
+        }
+
+        if let Some(side) = self.ball.update_four_walls(&mut self.paddles, events)
+            && self.result().is_none()
+        {
+            self.serve_after(side);
+        }
+    }
+
+    /// Serves again from the middle after `conceded` let the ball past, towards them if they're
+    /// still in or otherwise the next paddle along that is
+    fn serve_after(&mut self, conceded: Side) {
+        let start = SIDES.iter().position(|&side| side == conceded).unwrap_or(0);
+        let toward = (0..SIDES.len())
+            .map(|i| &self.paddles[(start + i) % SIDES.len()])
+            .find(|paddle| paddle.health() > 0)
+            .map_or(conceded, Paddle::side);
+
+        self.ball.serve_from_middle(&mut self.rng, toward);
+        self.countdown = COUNTDOWN_LENGTH;
+    }
+
+    /// The player loses as soon as they're knocked out, and wins once everyone else has been
+    pub fn result(&self) -> Option<MatchResult> {
+        let [player, others @ ..] = &self.paddles;
+        if player.health() == 0 {
+            Some(MatchResult::P2Win)
+        } else if others.iter().all(|paddle| paddle.health() == 0) {
+            Some(MatchResult::P1Win)
+        } else {
+            None
+        }
+    }
+
+    /// Turns what happened during the step into sounds
+    pub fn handle_events(&mut self, events: &mut EventSink, sounds: &mut FrameSounds) {
+        for event in events.drain() {
+            match event {
+                GameEvent::PaddleHit { pos, speed, .. } => {
+                    sounds.ball_paddle_hit.push(SoundCue::hit(pos.x, speed));
+                }
+                GameEvent::Bounce { pos, .. } | GameEvent::WallHit { pos, .. } => {
+                    sounds.wall_hit.push(SoundCue::at(pos.x));
+                }
+                GameEvent::BrickHit { .. } | GameEvent::Score { .. } => {}
+            }
+        }
+    }
+
+    pub fn show(&self, frame: &mut GraphicsFrame) {
+        let mut budget = SpriteBudget::new(frame);
+        for paddle in &self.paddles {
+            if paddle.health() > 0 {
+                paddle.show(&mut budget);
+            }
+            paddle.show_health(&mut budget);
+        }
+        self.paddles[0].show_charge(&mut budget);
+        self.ball.show(&mut budget);
+        show_countdown(self.countdown, &mut budget);
+        if let Some(selected) = self.paused {
+            show_pause_menu(selected, &mut budget);
+        }
+        budget.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paddle::start_pos;
+
+    #[test_case]
+    fn the_ball_costs_a_life_for_the_wall_it_crosses(_gba: &mut agb::Gba) {
+        let mut paddles = SIDES.map(|side| Paddle::new(side, start_pos(side), num!(2), 3));
+        let mut events = EventSink::default();
+
+        // Under the paddles at either end, heading right at a side that's already been knocked
+        // out, so it should bounce back and get past the player on the left instead
+        for _ in 0..3 {
+            paddles[1].lose_life();
+        }
+        let mut ball = Ball::new(vec2(num!(100), num!(120)), vec2(num!(3), num!(0)));
+        ball.launch();
+
+        let conceded = (0..1000)
+            .find_map(|_| ball.update_four_walls(&mut paddles, &mut events))
+            .expect("the ball should get past someone");
+        assert!(conceded == Side::Left);
+        assert_eq!(paddles[0].health(), 2);
+        assert_eq!(paddles[2].health(), 3);
+        assert_eq!(paddles[3].health(), 3);
+    }
+}
@@ -0,0 +1,241 @@
+//! Recording a match and playing it back. The random numbers all come from one seed and the game
+//! steps at a fixed rate, so the buttons pressed each frame and how many steps each frame took are
+//! all it takes to play the match out again exactly. That includes every serve, since they're
+//! launched with a button press.
+
+use agb::input::Button;
+use alloc::vec::Vec;
+
+use crate::GameConfig;
+use crate::input::Input;
+use crate::paddle::Difficulty;
+
+/// Everything a match was started with, so the replay can start it the same way
+#[derive(Clone, Copy)]
+pub struct ReplaySetup {
+    pub seed: u32,
+    pub config: GameConfig,
+    pub difficulty: Difficulty,
+    pub shared_controller: bool,
+}
+
+/// A stretch of frames that all had the same buttons held and the same number of steps. The
+/// buttons usually stay the same for a while, so storing runs keeps a whole match small enough
+/// to fit in memory.
+#[derive(Clone, Copy)]
+struct Run {
+    buttons: u16,
+    steps: u8,
+    frames: u8,
+}
+
+#[derive(Clone)]
+pub struct Recording {
+    setup: ReplaySetup,
+    /// What was held the frame before the recording started, so the first frame knows which
+    /// buttons had only just gone down
+    first_previous: u16,
+    runs: Vec<Run>,
+}
+
+pub struct Recorder {
+    recording: Recording,
+    /// This frame's input, waiting for its step count
+    pending: Option<Input>,
+}
+
+impl Recorder {
+    fn new(setup: ReplaySetup) -> Self {
+        Self {
+            recording: Recording {
+                setup,
+                first_previous: 0,
+                runs: Vec::new(),
+            },
+            pending: None,
+        }
+    }
+
+    fn record(&mut self, input: Input, steps: u32) {
+        let recording = &mut self.recording;
+        if recording.runs.is_empty() {
+            recording.first_previous = input.previous().bits() as u16;
+        }
+
+        let buttons = input.current().bits() as u16;
+        // The timestep never takes more than a handful of steps in one frame
+        let steps = steps.min(u32::from(u8::MAX)) as u8;
+        match recording.runs.last_mut() {
+            Some(run) if run.buttons == buttons && run.steps == steps && run.frames < u8::MAX => {
+                run.frames += 1;
+            }
+            _ => recording.runs.push(Run {
+                buttons,
+                steps,
+                frames: 1,
+            }),
+        }
+    }
+}
+
+pub struct Player {
+    recording: Recording,
+    /// The run being played and how many of its frames have gone by
+    run: usize,
+    frame: u8,
+    previous: u16,
+    /// The step count for this frame's input
+    pending_steps: u32,
+}
+
+impl Player {
+    fn new(recording: Recording) -> Self {
+        Self {
+            previous: recording.first_previous,
+            recording,
+            run: 0,
+            frame: 0,
+            pending_steps: 0,
+        }
+    }
+
+    /// The next frame's input and step count, or None once the recording has run out
+    fn next(&mut self) -> Option<(Input, u32)> {
+        let run = *self.recording.runs.get(self.run)?;
+
+        let buttons = Button::from_bits_truncate(run.buttons.into());
+        let input = Input::new(buttons, Button::from_bits_truncate(self.previous.into()));
+        self.previous = run.buttons;
+
+        self.frame += 1;
+        if self.frame >= run.frames {
+            self.run += 1;
+            self.frame = 0;
+        }
+        Some((input, u32::from(run.steps)))
+    }
+}
+
+/// Keeps the last match recorded, and whether one's being recorded or played back right now.
+/// During a match, call [`Replays::input`] and then [`Replays::steps`] once each per frame.
+#[derive(Default)]
+pub struct Replays {
+    recorder: Option<Recorder>,
+    player: Option<Player>,
+    last: Option<Recording>,
+}
+
+impl Replays {
+    /// Starts recording a new match, stopping anything that was going on before
+    pub fn start_recording(&mut self, setup: ReplaySetup) {
+        self.stop();
+        self.recorder = Some(Recorder::new(setup));
+    }
+
+    /// Stops recording or playing back. A finished recording becomes the one [`Replays::play`]
+    /// plays.
+    pub fn stop(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            self.last = Some(recorder.recording);
+        }
+        self.player = None;
+    }
+
+    /// Starts playing back the last recording, returning how to set up its match. Returns None
+    /// if nothing has been recorded yet.
+    pub fn play(&mut self) -> Option<ReplaySetup> {
+        self.stop();
+        let recording = self.last.clone()?;
+        let setup = recording.setup;
+        self.player = Some(Player::new(recording));
+        Some(setup)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.player.is_some()
+    }
+
+    /// The input to use this frame. That's `live` unless a replay is playing, in which case it's
+    /// the recorded input, or None once the replay has run out.
+    pub fn input(&mut self, live: Input) -> Option<Input> {
+        if let Some(player) = &mut self.player {
+            let (input, steps) = player.next()?;
+            player.pending_steps = steps;
+            return Some(input);
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.pending = Some(live);
+        }
+        Some(live)
+    }
+
+    /// How many steps to take this frame. That's `steps` unless a replay is playing, in which
+    /// case it's however many the recorded frame took.
+    pub fn steps(&mut self, steps: u32) -> u32 {
+        if let Some(player) = &mut self.player {
+            return core::mem::take(&mut player.pending_steps);
+        }
+
+        if let Some(recorder) = &mut self.recorder
+            && let Some(input) = recorder.pending.take()
+        {
+            recorder.record(input, steps);
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn playback_matches_what_was_recorded(_gba: &mut agb::Gba) {
+        let setup = ReplaySetup {
+            seed: 1234,
+            config: GameConfig::default(),
+            difficulty: Difficulty::Medium,
+            shared_controller: false,
+        };
+
+        // A few short presses, then A held for longer than a single run can cover
+        let pattern = [
+            (Button::empty(), 1),
+            (Button::A, 1),
+            (Button::A, 2),
+            (Button::UP, 1),
+            (Button::UP, 0),
+        ];
+        let mut frames = Vec::new();
+        let mut previous = Button::B;
+        for (current, steps) in pattern.iter().cycle().take(15).copied() {
+            frames.push((Input::new(current, previous), steps));
+            previous = current;
+        }
+        for _ in 0..600 {
+            frames.push((Input::new(Button::A, previous), 1));
+            previous = Button::A;
+        }
+
+        let mut replays = Replays::default();
+        replays.start_recording(setup);
+        for &(live, steps) in &frames {
+            assert!(replays.input(live) == Some(live));
+            assert_eq!(replays.steps(steps), steps);
+        }
+        replays.stop();
+
+        let played = replays.play().expect("there should be a recording to play");
+        assert_eq!(played.seed, setup.seed);
+        for &(recorded, steps) in &frames {
+            // The live input is ignored while the replay is playing
+            assert!(replays.input(Input::default()) == Some(recorded));
+            assert_eq!(replays.steps(99), steps);
+        }
+        assert!(
+            replays.input(Input::default()).is_none(),
+            "the replay should end where the recording did"
+        );
+    }
+}
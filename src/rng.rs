@@ -0,0 +1,78 @@
+//! A tiny xorshift random number generator. Given the same seed it always produces the same
+//! numbers, so anything random can be replayed exactly.
+
+use agb::fixnum::FixedNum;
+
+#[derive(Clone)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // Xorshift gets stuck on 0 forever, so swap it for an arbitrary non zero seed
+        let state = if seed == 0 { 0x9e37_79b9 } else { seed };
+        Self { state }
+    }
+
+    /// Where the generator has got to. Passing it to `new` carries on from the same point.
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// A number from `min` up to but not including `max`, or just `min` if the range is empty
+    pub fn range(&mut self, min: FixedNum<8>, max: FixedNum<8>) -> FixedNum<8> {
+        if max <= min {
+            return min;
+        }
+
+        let span = (max - min).to_raw() as u32;
+        min + FixedNum::from_raw((self.next_u32() % span) as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agb::fixnum::num;
+
+    #[test_case]
+    fn same_seed_gives_same_numbers(_gba: &mut agb::Gba) {
+        let mut a = Rng::new(1234);
+        let mut b = Rng::new(1234);
+
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test_case]
+    fn zero_seed_still_produces_numbers(_gba: &mut agb::Gba) {
+        let mut rng = Rng::new(0);
+        assert_ne!(rng.next_u32(), 0);
+    }
+
+    #[test_case]
+    fn range_stays_in_bounds(_gba: &mut agb::Gba) {
+        let mut rng = Rng::new(42);
+
+        for _ in 0..1000 {
+            let value = rng.range(num!(-2.5), num!(3));
+            assert!(
+                value >= num!(-2.5) && value < num!(3),
+                "{value} out of range"
+            );
+        }
+
+        assert_eq!(rng.range(num!(1), num!(1)), num!(1));
+    }
+}
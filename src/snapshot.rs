@@ -0,0 +1,418 @@
+//! Saving a match part way through, so it can be carried on after the console's been switched
+//! off. Only what it takes to pick the match back up is kept: the rules it was started with, the
+//! games and points won, and where the paddles and the ball were. Anything that comes and goes
+//! during a point, like power ups waiting on the field, gusts of wind and any extra balls, starts
+//! afresh.
+
+use agb::fixnum::{FixedNum, Vector2D, num, vec2};
+
+use crate::ball::{BALL_RADIUS, EdgeMode};
+use crate::obstacle::ObstacleLayout;
+use crate::paddle::{Difficulty, Paddle};
+use crate::rng::Rng;
+use crate::{
+    GamePlay, LIVES_CHOICES, MatchState, MatchStats, PauseItem, Settings, Side, WinCondition,
+};
+
+/// Starts every saved match, so an empty or scrambled save can't be mistaken for one
+const MAGIC: &[u8; 4] = b"PMAT";
+/// Goes up whenever the layout below changes, so a match saved by an older version of the game is
+/// ignored rather than loaded wrong
+const VERSION: u8 = 1;
+/// The length of a saved match, including the magic and version
+pub const SNAPSHOT_LEN: usize = 102;
+
+/// Where a paddle was and how it was doing
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct PaddleSnapshot {
+    pos: Vector2D<FixedNum<8>>,
+    velocity: FixedNum<8>,
+    score: u16,
+    health: u16,
+}
+
+/// Everything needed to carry on with a match
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct MatchSnapshot {
+    // The options the match was started with, which set its rules
+    difficulty: Difficulty,
+    win_condition: WinCondition,
+    lives: u16,
+    large_ball: bool,
+    mercy_rule: bool,
+    edge_mode: EdgeMode,
+    rubber_band: bool,
+    multiball: bool,
+    obstacles: ObstacleLayout,
+    gravity: bool,
+    wind: bool,
+
+    games_won: [u8; 2],
+    rng: u32,
+    paddles: [PaddleSnapshot; 2],
+    ball_pos: Vector2D<FixedNum<8>>,
+    ball_velocity: Vector2D<FixedNum<8>>,
+    rally: u32,
+    /// Who was holding the ball ready to serve, if it wasn't in play
+    server: Option<Side>,
+    countdown: u16,
+    time_left: u32,
+    overtime: bool,
+    stats: MatchStats,
+}
+
+impl MatchSnapshot {
+    /// Takes a snapshot of `match_state`, unless it's a match that can't be carried on later. The
+    /// demo and link games can't, and shared controller practice isn't worth keeping.
+    pub fn take(match_state: &MatchState) -> Option<Self> {
+        let game = &match_state.game;
+        if game.demo || game.link_role.is_some() || game.shared_controller {
+            return None;
+        }
+
+        let paddle = |paddle: &Paddle| PaddleSnapshot {
+            pos: paddle.pos(),
+            velocity: paddle.velocity(),
+            score: paddle.score(),
+            health: paddle.health(),
+        };
+        let ball = &game.balls[0];
+        Some(Self {
+            difficulty: game.ai.difficulty(),
+            win_condition: game.config.win_condition,
+            lives: game.config.starting_health,
+            large_ball: game.config.ball_radius > BALL_RADIUS,
+            mercy_rule: game.config.mercy_margin.is_some(),
+            edge_mode: game.config.edge_mode,
+            rubber_band: game.config.rubber_band,
+            multiball: game.config.multiball,
+            obstacles: game.config.obstacles,
+            gravity: game.config.gravity.y != num!(0),
+            wind: game.config.wind,
+            games_won: match_state.games_won,
+            rng: game.rng.state(),
+            paddles: [paddle(&game.paddle_a), paddle(&game.paddle_b)],
+            ball_pos: ball.pos(),
+            ball_velocity: ball.velocity(),
+            rally: ball.rally(),
+            server: ball.server(),
+            countdown: game.countdown,
+            time_left: game.time_left,
+            overtime: game.overtime,
+            stats: game.stats,
+        })
+    }
+
+    /// Sets the match up again just as it was when the snapshot was taken. It comes back paused,
+    /// so the player has a moment to get ready.
+    pub fn restore(&self) -> MatchState {
+        let settings = Settings {
+            difficulty: self.difficulty,
+            win_condition: self.win_condition,
+            lives: self.lives,
+            large_ball: self.large_ball,
+            mercy_rule: self.mercy_rule,
+            edge_mode: self.edge_mode,
+            rubber_band: self.rubber_band,
+            multiball: self.multiball,
+            obstacles: self.obstacles,
+            gravity: self.gravity,
+            wind: self.wind,
+            ..Settings::default()
+        };
+        let config = settings.game_config();
+
+        let mut game = GamePlay::new(false, self.difficulty, config, Rng::new(self.rng));
+        game.rng = Rng::new(self.rng);
+        for (paddle, saved) in [&mut game.paddle_a, &mut game.paddle_b]
+            .into_iter()
+            .zip(&self.paddles)
+        {
+            paddle.resume(saved.pos, saved.velocity, saved.score, saved.health);
+        }
+        game.balls[0].resume(self.ball_pos, self.ball_velocity, self.rally, self.server);
+        game.countdown = self.countdown;
+        game.time_left = self.time_left;
+        game.overtime = self.overtime;
+        game.stats = self.stats;
+        game.paused = Some(PauseItem::Resume);
+
+        let mut match_state = MatchState::new(game, config.games_to_win);
+        match_state.games_won = self.games_won;
+        match_state.saved = true;
+        match_state
+    }
+
+    pub fn serialize(&self) -> [u8; SNAPSHOT_LEN] {
+        let mut buffer = [0; SNAPSHOT_LEN];
+        let mut writer = Writer {
+            buffer: &mut buffer,
+            at: 0,
+        };
+
+        writer.bytes(MAGIC);
+        writer.bytes(&[VERSION]);
+        writer.bytes(&[
+            self.difficulty.index(),
+            self.win_condition.index(),
+            self.lives as u8,
+            self.large_ball.into(),
+            self.mercy_rule.into(),
+            (self.edge_mode == EdgeMode::Wrap).into(),
+            self.rubber_band.into(),
+            self.multiball.into(),
+            self.gravity.into(),
+            self.wind.into(),
+            self.obstacles.index(),
+        ]);
+        writer.bytes(&self.games_won);
+        writer.u32(self.rng);
+        for paddle in &self.paddles {
+            writer.vector(paddle.pos);
+            writer.fixed(paddle.velocity);
+            writer.u16(paddle.score);
+            writer.u16(paddle.health);
+        }
+        writer.vector(self.ball_pos);
+        writer.vector(self.ball_velocity);
+        writer.u32(self.rally);
+        writer.bytes(&[match self.server {
+            None => 0,
+            Some(Side::Left) => 1,
+            Some(Side::Right | Side::Top | Side::Bottom) => 2,
+        }]);
+        writer.u16(self.countdown);
+        writer.u32(self.time_left);
+        writer.bytes(&[self.overtime.into()]);
+        let stats = &self.stats;
+        for value in [stats.longest_rally, stats.hits, stats.points, stats.frames] {
+            writer.u32(value);
+        }
+        for score in stats.score {
+            writer.u16(score);
+        }
+
+        debug_assert_eq!(writer.at, SNAPSHOT_LEN);
+        buffer
+    }
+
+    /// Reads back a snapshot written by `serialize`, or None if it isn't one or it was written by
+    /// a different version of the game
+    pub fn deserialize(buffer: &[u8; SNAPSHOT_LEN]) -> Option<Self> {
+        let mut reader = Reader { buffer, at: 0 };
+        if reader.bytes::<4>() != *MAGIC || reader.u8() != VERSION {
+            return None;
+        }
+
+        let difficulty = Difficulty::from_index(reader.u8())?;
+        let win_condition = WinCondition::from_index(reader.u8())?;
+        let lives = reader.u8().into();
+        if !LIVES_CHOICES.contains(&lives) {
+            return None;
+        }
+        let [
+            large_ball,
+            mercy_rule,
+            wrap,
+            rubber_band,
+            multiball,
+            gravity,
+            wind,
+        ] = reader.bytes().map(|byte| byte == 1);
+        let obstacles = ObstacleLayout::from_index(reader.u8())?;
+        let edge_mode = if wrap {
+            EdgeMode::Wrap
+        } else {
+            EdgeMode::Bounce
+        };
+        let games_won = reader.bytes();
+        let rng = reader.u32();
+        let mut paddle = || PaddleSnapshot {
+            pos: reader.vector(),
+            velocity: reader.fixed(),
+            score: reader.u16(),
+            health: reader.u16(),
+        };
+        let paddles = [paddle(), paddle()];
+        let ball_pos = reader.vector();
+        let ball_velocity = reader.vector();
+        let rally = reader.u32();
+        let server = match reader.u8() {
+            0 => None,
+            1 => Some(Side::Left),
+            2 => Some(Side::Right),
+            _ => return None,
+        };
+        let countdown = reader.u16();
+        let time_left = reader.u32();
+        let overtime = reader.u8() == 1;
+        let stats = MatchStats {
+            longest_rally: reader.u32(),
+            hits: reader.u32(),
+            points: reader.u32(),
+            frames: reader.u32(),
+            score: [reader.u16(), reader.u16()],
+        };
+
+        Some(Self {
+            difficulty,
+            win_condition,
+            lives,
+            large_ball,
+            mercy_rule,
+            edge_mode,
+            rubber_band,
+            multiball,
+            obstacles,
+            gravity,
+            wind,
+            games_won,
+            rng,
+            paddles,
+            ball_pos,
+            ball_velocity,
+            rally,
+            server,
+            countdown,
+            time_left,
+            overtime,
+            stats,
+        })
+    }
+}
+
+/// Fills a buffer from the start, little endian like the rest of the save
+struct Writer<'a> {
+    buffer: &'a mut [u8; SNAPSHOT_LEN],
+    at: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buffer[self.at..self.at + bytes.len()].copy_from_slice(bytes);
+        self.at += bytes.len();
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn fixed(&mut self, value: FixedNum<8>) {
+        self.bytes(&value.to_raw().to_le_bytes());
+    }
+
+    fn vector(&mut self, value: Vector2D<FixedNum<8>>) {
+        self.fixed(value.x);
+        self.fixed(value.y);
+    }
+}
+
+/// Reads a buffer written by [`Writer`] back in the same order
+struct Reader<'a> {
+    buffer: &'a [u8; SNAPSHOT_LEN],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&self.buffer[self.at..self.at + N]);
+        self.at += N;
+        bytes
+    }
+
+    fn u8(&mut self) -> u8 {
+        let [byte] = self.bytes();
+        byte
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.bytes())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.bytes())
+    }
+
+    fn fixed(&mut self) -> FixedNum<8> {
+        FixedNum::from_raw(i32::from_le_bytes(self.bytes()))
+    }
+
+    fn vector(&mut self) -> Vector2D<FixedNum<8>> {
+        let x = self.fixed();
+        vec2(x, self.fixed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventSink;
+    use crate::input::Input;
+    use crate::{FrameSounds, GameConfig};
+    use agb::input::Button;
+
+    fn match_in_progress() -> MatchState {
+        let settings = Settings {
+            difficulty: Difficulty::Hard,
+            edge_mode: EdgeMode::Wrap,
+            ..Settings::default()
+        };
+        let game = GamePlay::new(false, Difficulty::Hard, settings.game_config(), Rng::new(5));
+        let mut match_state = MatchState::new(game, GameConfig::default().games_to_win);
+        match_state.games_won = [1, 0];
+
+        // Serve and play on for a bit, so nothing's where it started
+        let mut events = EventSink::default();
+        let mut sounds = FrameSounds::default();
+        for step in 0..400 {
+            let buttons = if step % 50 < 25 {
+                Button::A | Button::DOWN
+            } else {
+                Button::UP
+            };
+            let input = Input::new(buttons, Button::empty());
+            match_state.game.step(&input, &mut events, &mut sounds);
+        }
+        match_state
+    }
+
+    #[test_case]
+    fn a_saved_match_comes_back_the_same(_gba: &mut agb::Gba) {
+        let snapshot = MatchSnapshot::take(&match_in_progress()).expect("the match can be saved");
+        let loaded = MatchSnapshot::deserialize(&snapshot.serialize()).expect("it should load");
+        assert!(loaded == snapshot);
+
+        let restored = MatchSnapshot::take(&loaded.restore()).expect("it can be saved again");
+        assert!(
+            restored == snapshot,
+            "the restored match should be the one that was saved"
+        );
+    }
+
+    #[test_case]
+    fn saves_from_other_versions_are_ignored(_gba: &mut agb::Gba) {
+        let snapshot = MatchSnapshot::take(&match_in_progress()).expect("the match can be saved");
+        let mut buffer = snapshot.serialize();
+        buffer[MAGIC.len()] = VERSION + 1;
+        assert!(MatchSnapshot::deserialize(&buffer).is_none());
+
+        assert!(
+            MatchSnapshot::deserialize(&[0; SNAPSHOT_LEN]).is_none(),
+            "blank memory isn't a saved match"
+        );
+    }
+
+    #[test_case]
+    fn saves_with_impossible_lives_are_ignored(_gba: &mut agb::Gba) {
+        let snapshot = MatchSnapshot::take(&match_in_progress()).expect("the match can be saved");
+        let mut buffer = snapshot.serialize();
+        // After the magic, the version, the difficulty and the win condition
+        buffer[MAGIC.len() + 3] = 200;
+        assert!(MatchSnapshot::deserialize(&buffer).is_none());
+    }
+}
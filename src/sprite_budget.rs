@@ -0,0 +1,155 @@
+//! Keeps the busiest frames inside the hardware's limit on objects. Everything in a match is shown
+//! through a [`SpriteBudget`], which counts the objects as they go by. What matters gets shown
+//! straight away, and the purely decorative objects are held back until the end of the frame, when
+//! only as many as there's room left for are shown.
+//!
+//! From most to least important, which is the order they're shown in:
+//!
+//! 1. The balls, paddles, power ups, obstacles, bricks and wind
+//! 2. The scores, lives, clock, banners and pause menu
+//! 3. The debug overlay
+//! 4. The ball trails, newest ghost first
+//! 5. The particles
+//!
+//! So in a busy moment the particles go first, then the oldest ghosts of every ball, and the game
+//! itself never loses a sprite.
+
+use agb::display::GraphicsFrame;
+use agb::display::object::Object;
+use alloc::vec::Vec;
+
+/// How many objects the GBA can show at once
+pub const OBJECT_LIMIT: usize = 128;
+
+/// The objects that are only there for show, least important last
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Decoration {
+    /// A ghost following a ball, with how many places back along the trail it is. The newest
+    /// ghost of every ball goes before the next oldest of any of them.
+    Trail(usize),
+    /// A spark thrown out by a collision
+    Particle,
+}
+
+/// Somewhere objects can be shown, either the frame itself or a [`SpriteBudget`] that counts them
+/// on the way
+pub trait ObjectSink {
+    fn show(&mut self, object: &Object);
+}
+
+impl ObjectSink for GraphicsFrame<'_> {
+    fn show(&mut self, object: &Object) {
+        object.show(self);
+    }
+}
+
+/// Counts the objects shown on `frame`, and holds on to its decorations until everything else has
+/// been shown
+pub struct SpriteBudget<'a, 'frame> {
+    frame: &'a mut GraphicsFrame<'frame>,
+    /// How many objects have gone on the frame so far, decorations included
+    shown: usize,
+    decorations: Vec<(Decoration, Object)>,
+}
+
+impl<'a, 'frame> SpriteBudget<'a, 'frame> {
+    pub fn new(frame: &'a mut GraphicsFrame<'frame>) -> Self {
+        Self {
+            frame,
+            shown: 0,
+            decorations: Vec::new(),
+        }
+    }
+
+    /// Holds on to `object` until the end of the frame
+    pub fn decorate(&mut self, kind: Decoration, object: Object) {
+        self.decorations.push((kind, object));
+    }
+
+    /// How many more objects the hardware has room for this frame
+    fn room(&self) -> usize {
+        OBJECT_LIMIT.saturating_sub(self.shown)
+    }
+
+    /// Puts the decorations in the order they're shown in, keeping them in the order they came
+    /// in where they're just as important
+    fn sort(&mut self) {
+        self.decorations.sort_by_key(|&(kind, _)| kind);
+    }
+
+    /// Shows as many of the decorations as there's room left for after everything else in the
+    /// frame, most important first, so it's the least important ones that don't fit
+    pub fn finish(mut self) {
+        self.sort();
+        let room = self.room();
+        for (_, object) in self.decorations.iter().take(room) {
+            object.show(self.frame);
+        }
+    }
+}
+
+impl ObjectSink for SpriteBudget<'_, '_> {
+    fn show(&mut self, object: &Object) {
+        self.shown += 1;
+        object.show(self.frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprites;
+
+    #[test_case]
+    fn oldest_ghosts_and_particles_go_last(gba: &mut agb::Gba) {
+        let mut gfx = gba.graphics.get();
+        let mut frame = gfx.frame();
+        let mut budget = SpriteBudget::new(&mut frame);
+        for kind in [
+            Decoration::Particle,
+            Decoration::Trail(1),
+            Decoration::Trail(0),
+            Decoration::Particle,
+            Decoration::Trail(1),
+            Decoration::Trail(0),
+        ] {
+            budget.decorate(kind, Object::new(sprites::PARTICLE.sprite(0)));
+        }
+
+        budget.sort();
+        let kinds: Vec<_> = budget.decorations.iter().map(|&(kind, _)| kind).collect();
+        assert!(
+            kinds
+                == [
+                    Decoration::Trail(0),
+                    Decoration::Trail(0),
+                    Decoration::Trail(1),
+                    Decoration::Trail(1),
+                    Decoration::Particle,
+                    Decoration::Particle,
+                ]
+        );
+    }
+
+    #[test_case]
+    fn decorations_only_get_the_room_left_over(gba: &mut agb::Gba) {
+        let mut gfx = gba.graphics.get();
+        let mut frame = gfx.frame();
+        let mut budget = SpriteBudget::new(&mut frame);
+
+        let object = Object::new(sprites::PARTICLE.sprite(0));
+        for _ in 0..OBJECT_LIMIT - 2 {
+            budget.show(&object);
+        }
+        assert_eq!(budget.room(), 2);
+
+        for _ in 0..4 {
+            budget.show(&object);
+        }
+        assert_eq!(
+            budget.room(),
+            0,
+            "going over the limit leaves no room at all"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Colour themes, which recolour the whole screen by rewriting the palettes rather than needing a
+//! second copy of the graphics.
+
+use agb::display::tiled::VRAM_MANAGER;
+use agb::display::{Palette16, Rgb15};
+use alloc::vec::Vec;
+
+/// Where the object palettes live. agb doesn't have a way to change these once it's loaded them,
+/// so the theme writes to them directly.
+const OBJECT_PALETTES: *mut Rgb15 = 0x0500_0200 as *mut Rgb15;
+const PALETTE_COUNT: usize = 16;
+const PALETTE_SIZE: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    /// The colours the graphics were drawn in
+    Classic,
+    /// Shades of green like an old monochrome monitor
+    Green,
+    /// Shades of orange like an old amber monitor
+    Amber,
+    /// Brighter, more saturated colours on a purple tinted background
+    Neon,
+}
+
+const THEMES: [Theme; 4] = [Theme::Classic, Theme::Green, Theme::Amber, Theme::Neon];
+
+/// Splits a colour into its 5 bit red, green and blue parts
+fn channels(colour: Rgb15) -> [i32; 3] {
+    [0, 5, 10].map(|shift| i32::from((colour.0 >> shift) & 0x1f))
+}
+
+fn from_channels(channels: [i32; 3]) -> Rgb15 {
+    let [r, g, b] = channels.map(|channel| channel.clamp(0, 0x1f) as u16);
+    Rgb15(r | (g << 5) | (b << 10))
+}
+
+impl Theme {
+    /// Where the theme is in the list, which is also how it's saved
+    pub fn index(self) -> u8 {
+        THEMES.iter().position(|&theme| theme == self).unwrap_or(0) as u8
+    }
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        THEMES.get(usize::from(index)).copied()
+    }
+
+    /// The theme `steps` along the list from this one, wrapping around at either end
+    pub fn cycle(self, steps: i32) -> Self {
+        let index = (i32::from(self.index()) + steps).rem_euclid(THEMES.len() as i32);
+        THEMES[index as usize]
+    }
+
+    /// What `colour` looks like in this theme
+    fn recolour(self, colour: Rgb15) -> Rgb15 {
+        let [r, g, b] = channels(colour);
+        // Weighted the way eyes see brightness, so the picture keeps the same light and dark
+        let brightness = (r * 2 + g * 5 + b) / 8;
+
+        // The monochrome themes blend from their darkest shade to their lightest by brightness
+        let tint = |dark: [i32; 3], light: [i32; 3]| {
+            from_channels(core::array::from_fn(|i| {
+                dark[i] + (light[i] - dark[i]) * brightness / 0x1f
+            }))
+        };
+
+        match self {
+            Theme::Classic => colour,
+            Theme::Green => tint([1, 3, 1], [20, 31, 12]),
+            Theme::Amber => tint([3, 1, 0], [31, 24, 8]),
+            Theme::Neon => {
+                // Push each channel twice as far from the brightness, then tint the darkest
+                // colours purple so the black background doesn't look the same as the classic one
+                let purple = [4, 0, 8];
+                let mut neon = [r, g, b];
+                for (channel, purple) in neon.iter_mut().zip(purple) {
+                    *channel += *channel - brightness + purple * (0x1f - brightness) / 0x1f;
+                }
+                from_channels(neon)
+            }
+        }
+    }
+}
+
+/// Keeps the palettes in the current theme. agb loads each sprite's palette into object palette
+/// memory the first time it's shown, with the colours it was drawn in, so `update` needs calling
+/// every frame to recolour any new ones as they turn up. Only palettes that have changed get
+/// recoloured, so a frame where nothing new was loaded just compares them.
+pub struct Palettes {
+    theme: Theme,
+    backgrounds: &'static [Palette16],
+    /// The colours each object palette was loaded with, before the theme was applied
+    originals: [[Rgb15; PALETTE_SIZE]; PALETTE_COUNT],
+    /// The themed colours last written to each object palette, so it's clear when agb loads a
+    /// different one over the top
+    themed: [[Rgb15; PALETTE_SIZE]; PALETTE_COUNT],
+    /// Whether the theme has changed since the object palettes were last written, so they all
+    /// need recolouring
+    dirty: bool,
+}
+
+impl Palettes {
+    pub fn new(theme: Theme, backgrounds: &'static [Palette16]) -> Self {
+        let mut palettes = Self {
+            theme,
+            backgrounds,
+            originals: [[Rgb15(0); PALETTE_SIZE]; PALETTE_COUNT],
+            themed: [[Rgb15(0); PALETTE_SIZE]; PALETTE_COUNT],
+            dirty: true,
+        };
+        palettes.set_theme(theme);
+        palettes
+    }
+
+    /// Switches to `theme`, recolouring the backgrounds and sprites together
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+
+        let backgrounds: Vec<_> = self
+            .backgrounds
+            .iter()
+            .map(|palette| {
+                let mut palette = palette.clone();
+                for i in 0..PALETTE_SIZE {
+                    palette.update_colour(i, theme.recolour(palette.colour(i)));
+                }
+                palette
+            })
+            .collect();
+        VRAM_MANAGER.set_background_palettes(&backgrounds);
+
+        self.dirty = true;
+        self.update();
+    }
+
+    /// Recolours any object palettes agb has loaded since the last update, or all of them if the
+    /// theme has changed
+    pub fn update(&mut self) {
+        for palette in 0..PALETTE_COUNT {
+            // Safety: stays inside object palette memory, which agb only writes to when it loads
+            // a palette
+            let address = |i: usize| unsafe { OBJECT_PALETTES.add(palette * PALETTE_SIZE + i) };
+
+            let mut changed = self.dirty;
+            for i in 0..PALETTE_SIZE {
+                // Safety: as above
+                let current = unsafe { address(i).read_volatile() };
+                if current != self.themed[palette][i] {
+                    self.originals[palette][i] = current;
+                    changed = true;
+                }
+            }
+            if !changed {
+                continue;
+            }
+
+            for i in 0..PALETTE_SIZE {
+                let themed = self.theme.recolour(self.originals[palette][i]);
+                self.themed[palette][i] = themed;
+                // Safety: as above
+                unsafe { address(i).write_volatile(themed) };
+            }
+        }
+        self.dirty = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agb::display::Rgb;
+
+    #[test_case]
+    fn classic_leaves_colours_alone(_gba: &mut agb::Gba) {
+        let colour = Rgb::new(228, 59, 68).to_rgb15();
+        assert!(Theme::Classic.recolour(colour) == colour);
+    }
+
+    #[test_case]
+    fn themes_keep_light_and_dark_apart(_gba: &mut agb::Gba) {
+        let brightness = |colour: Rgb15| {
+            let [r, g, b] = channels(colour);
+            r * 2 + g * 5 + b
+        };
+
+        for theme in THEMES {
+            let black = theme.recolour(Rgb15::BLACK);
+            let white = theme.recolour(Rgb15::WHITE);
+            assert!(
+                brightness(white) > brightness(black) * 4,
+                "text should still stand out from the background"
+            );
+            assert!(Theme::from_index(theme.index()) == Some(theme));
+        }
+    }
+}
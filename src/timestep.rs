@@ -0,0 +1,142 @@
+//! Keeps the game running at the same speed even when drawing a frame takes too long, by counting
+//! the vblanks that have gone by and stepping the game once for each of them.
+
+use portable_atomic::{AtomicU32, Ordering};
+
+use agb::fixnum::{FixedNum, num};
+use agb::interrupt::{Interrupt, InterruptHandler, add_interrupt_handler};
+
+/// How many vblanks each step of the game covers. At 1 the game steps 60 times a second, which is
+/// what all the speeds are tuned for.
+pub const VBLANKS_PER_STEP: u32 = 1;
+/// The most steps taken to catch up in one go. If a frame takes far too long it's better for the
+/// game to slow down briefly than to jump ahead past where the player could react.
+const MAX_STEPS_PER_FRAME: u32 = 4;
+
+/// The GBA's screen refreshes about 59.73 times a second rather than 60, which adds up to most of
+/// a second over a few minutes. That comes out to a whole number of vblanks an hour.
+const VBLANKS_PER_HOUR: u64 = 215_019;
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+
+static VBLANKS: AtomicU32 = AtomicU32::new(0);
+
+/// How many full speed steps of the game it takes for `seconds` to go by, leaving off any part
+/// step at the end
+pub fn steps_for_seconds(seconds: u32) -> u32 {
+    let vblanks = u64::from(seconds) * VBLANKS_PER_HOUR / SECONDS_PER_HOUR;
+    (vblanks / u64::from(VBLANKS_PER_STEP)) as u32
+}
+
+/// How many seconds `steps` full speed steps take, rounded up so a clock counting down only
+/// shows 0 once it's run out
+pub fn seconds_for_steps(steps: u32) -> u32 {
+    let vblanks = u64::from(steps) * u64::from(VBLANKS_PER_STEP);
+    (vblanks * SECONDS_PER_HOUR).div_ceil(VBLANKS_PER_HOUR) as u32
+}
+
+pub struct FixedTimestep {
+    /// Keeps the vblank counter running, it stops as soon as this is dropped
+    _counter: InterruptHandler,
+    /// The vblank count the last step was taken at
+    last: u32,
+    /// The part of a step built up by running slower than full speed
+    partial: FixedNum<8>,
+}
+
+impl FixedTimestep {
+    pub fn new() -> Self {
+        // Safety: only touches an atomic, so doesn't allocate
+        let counter = unsafe {
+            add_interrupt_handler(Interrupt::VBlank, |_| {
+                // Only the interrupt writes to the count, so this doesn't need to be a single
+                // atomic operation
+                VBLANKS.store(
+                    VBLANKS.load(Ordering::SeqCst).wrapping_add(1),
+                    Ordering::SeqCst,
+                );
+            })
+        };
+
+        Self {
+            _counter: counter,
+            last: VBLANKS.load(Ordering::SeqCst),
+            partial: num!(0),
+        }
+    }
+
+    /// How many times the game should step this frame to catch up with the vblanks since the
+    /// last call. This is normally 1, but is 0 if not enough time has passed yet.
+    pub fn steps(&mut self) -> u32 {
+        self.steps_at(VBLANKS.load(Ordering::SeqCst))
+    }
+
+    fn steps_at(&mut self, now: u32) -> u32 {
+        let steps = now.wrapping_sub(self.last) / VBLANKS_PER_STEP;
+        self.last = self.last.wrapping_add(steps * VBLANKS_PER_STEP);
+
+        if steps > MAX_STEPS_PER_FRAME {
+            // Drop the steps we can't take rather than saving them up for later
+            self.last = now;
+            return MAX_STEPS_PER_FRAME;
+        }
+
+        steps
+    }
+
+    /// Scales `steps` by `speed`, carrying the leftover part of a step over to the next frame. At
+    /// half speed this steps every other frame, and at double speed twice a frame, while the
+    /// screen is still drawn once a frame.
+    pub fn scaled(&mut self, steps: u32, speed: FixedNum<8>) -> u32 {
+        self.partial += speed * steps as i32;
+        let whole = self.partial.floor();
+        self.partial -= whole;
+        whole as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn steps_catch_up_with_missed_vblanks(_gba: &mut agb::Gba) {
+        let mut timestep = FixedTimestep::new();
+        let start = timestep.last;
+
+        assert_eq!(timestep.steps_at(start), 0);
+        assert_eq!(timestep.steps_at(start + VBLANKS_PER_STEP), 1);
+        assert_eq!(timestep.steps_at(start + 3 * VBLANKS_PER_STEP), 2);
+        assert_eq!(
+            timestep.steps_at(start + 100 * VBLANKS_PER_STEP),
+            MAX_STEPS_PER_FRAME
+        );
+        assert_eq!(timestep.steps_at(start + 100 * VBLANKS_PER_STEP), 0);
+    }
+
+    #[test_case]
+    fn a_minute_of_steps_is_a_minute(_gba: &mut agb::Gba) {
+        let minute = steps_for_seconds(60);
+        assert_eq!(minute, 3583 / VBLANKS_PER_STEP);
+        assert_eq!(seconds_for_steps(minute), 60);
+        assert_eq!(
+            seconds_for_steps(minute - 1),
+            60,
+            "a part second still counts"
+        );
+        assert_eq!(seconds_for_steps(0), 0);
+    }
+
+    #[test_case]
+    fn scaled_steps_add_up(_gba: &mut agb::Gba) {
+        let mut timestep = FixedTimestep::new();
+
+        let steps: u32 = (0..8).map(|_| timestep.scaled(1, num!(0.75))).sum();
+        assert_eq!(steps, 6);
+        assert_eq!(
+            timestep.scaled(1, num!(1)),
+            1,
+            "full speed takes every step"
+        );
+        assert_eq!(timestep.scaled(2, num!(2)), 4, "turbo doubles up");
+    }
+}
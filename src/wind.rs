@@ -0,0 +1,130 @@
+//! Gusts of wind that blow the balls sideways every so often. An arrow near the bottom of the
+//! screen flashes to warn which way the next gust is coming from, and stays up while it blows.
+
+use agb::display::object::Object;
+use agb::display::{HEIGHT, Priority, WIDTH};
+use agb::fixnum::{FixedNum, Vector2D, num, vec2};
+
+use crate::rng::Rng;
+use crate::sprite_budget::ObjectSink;
+use crate::{fixed, sprites};
+
+/// How long it stays calm between gusts
+const CALM_FRAMES: u16 = 60 * 6;
+/// How long the arrow flashes before a gust starts, so the players can get ready for it
+const WARNING_FRAMES: u16 = 60;
+const GUST_FRAMES: u16 = 60 * 3;
+/// How hard a gust can push the ball each frame, up to but not including the max. Even the
+/// strongest one only changes the ball's speed by a couple of pixels per frame over the whole
+/// gust.
+const MIN_STRENGTH: FixedNum<8> = fixed(0.008);
+const MAX_STRENGTH: FixedNum<8> = fixed(0.016);
+/// How fast the warning arrow flashes
+const FLASH_FRAMES: u16 = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Calm,
+    Warning,
+    Gust,
+}
+
+pub struct Wind {
+    phase: Phase,
+    /// Frames left before moving on to the next phase
+    timer: u16,
+    /// How hard the next or current gust pushes, negative for towards the left
+    strength: FixedNum<8>,
+}
+
+impl Wind {
+    pub fn new() -> Self {
+        Self {
+            phase: Phase::Calm,
+            timer: CALM_FRAMES,
+            strength: num!(0),
+        }
+    }
+
+    /// Moves the weather on by a frame, picking the strength and direction of each gust when its
+    /// warning starts
+    pub fn update(&mut self, rng: &mut Rng) {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+
+        (self.phase, self.timer) = match self.phase {
+            Phase::Calm => {
+                self.strength = rng.range(MIN_STRENGTH, MAX_STRENGTH);
+                if rng.next_u32().is_multiple_of(2) {
+                    self.strength = -self.strength;
+                }
+                (Phase::Warning, WARNING_FRAMES)
+            }
+            Phase::Warning => (Phase::Gust, GUST_FRAMES),
+            Phase::Gust => (Phase::Calm, CALM_FRAMES),
+        };
+    }
+
+    /// How much the wind pushes a ball this frame
+    pub fn force(&self) -> Vector2D<FixedNum<8>> {
+        if self.phase == Phase::Gust {
+            vec2(self.strength, num!(0))
+        } else {
+            vec2(num!(0), num!(0))
+        }
+    }
+
+    pub fn show(&self, frame: &mut impl ObjectSink) {
+        let visible = match self.phase {
+            Phase::Calm => false,
+            Phase::Warning => (self.timer / FLASH_FRAMES).is_multiple_of(2),
+            Phase::Gust => true,
+        };
+        if !visible {
+            return;
+        }
+
+        // The arrow points right, so it's flipped for a gust blowing left
+        frame.show(
+            Object::new(sprites::WIND.sprite(0))
+                .set_pos(vec2(WIDTH / 2 - 8, HEIGHT - 24))
+                .set_hflip(self.strength < num!(0))
+                .set_priority(Priority::P1),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn gusts_are_announced_and_bounded(_gba: &mut agb::Gba) {
+        let mut wind = Wind::new();
+        let mut rng = Rng::new(7);
+        let mut gusts = 0;
+        let mut last_phase = wind.phase;
+
+        for _ in 0..(CALM_FRAMES + WARNING_FRAMES + GUST_FRAMES) * 4 {
+            wind.update(&mut rng);
+
+            if wind.phase != last_phase {
+                if wind.phase == Phase::Gust {
+                    assert!(
+                        last_phase == Phase::Warning,
+                        "every gust should be warned about"
+                    );
+                    gusts += 1;
+                }
+                last_phase = wind.phase;
+            }
+
+            let force = wind.force().x.abs();
+            assert!(force <= MAX_STRENGTH, "{force} is too strong");
+        }
+
+        assert!(gusts >= 3, "the wind should keep coming back");
+    }
+}